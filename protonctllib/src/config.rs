@@ -0,0 +1,596 @@
+// User configuration, read from config.toml in the XDG config directory or PROTONCTL_CONFIG
+use crate::constants;
+use crate::error::{Error, Result};
+use crate::github::api::{self, Client};
+use crate::install_type::InstallType;
+use crate::source::CustomSource;
+use crate::target::Target;
+use crate::utils;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// Environment variable pointing at a config file to use instead of the default one
+pub const CONFIG_ENV: &str = "PROTONCTL_CONFIG";
+
+// Keys accepted by config get and config set. sources can only be edited in the file.
+pub const KEYS: &[&str] = &[
+    "defaults.type",
+    "defaults.number",
+    "paths.download",
+    "paths.data",
+    "paths.proton",
+    "paths.proton_flatpak",
+    "paths.proton_snap",
+    "paths.wine",
+    "paths.wine_flatpak",
+    "paths.ulwgl",
+    "styles.prefix",
+    "styles.success",
+    "styles.fail",
+    "styles.label",
+    "styles.version",
+    "styles.url",
+    "styles.changelog",
+    "styles.header",
+    "styles.directory",
+    "styles.status",
+    "api.url",
+    "api.token",
+    "api.timeout",
+    "api.connect_timeout",
+    "api.retries",
+    "api.proxy",
+    "api.ca_certificate",
+];
+
+// Keys holding whole numbers, everything else is a string
+const INTEGER_KEYS: &[&str] = &[
+    "defaults.number",
+    "api.timeout",
+    "api.connect_timeout",
+    "api.retries",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub defaults: Defaults,
+    #[serde(default)]
+    pub paths: Paths,
+    #[serde(default)]
+    pub styles: Styles,
+    #[serde(default)]
+    pub api: Api,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<CustomSource>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields, default)]
+pub struct Defaults {
+    // Used when -t isn't given
+    #[serde(rename = "type")]
+    pub install_type: String,
+    // Number of releases list shows when -n isn't given
+    pub number: u8,
+}
+
+impl Default for Defaults {
+    fn default() -> Self {
+        Self {
+            install_type: InstallType::Proton.to_string(),
+            number: 10,
+        }
+    }
+}
+
+// Directories are relative to the home directory unless absolute. Unset directories use the
+// XDG directories and the detected Steam root.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Paths {
+    // Downloads, in the XDG cache directory by default
+    pub download: Option<PathBuf>,
+    // The install manifest, in the XDG data directory by default
+    pub data: Option<PathBuf>,
+    pub proton: Option<PathBuf>,
+    pub proton_flatpak: Option<PathBuf>,
+    pub proton_snap: Option<PathBuf>,
+    pub wine: Option<PathBuf>,
+    pub wine_flatpak: Option<PathBuf>,
+    pub ulwgl: Option<PathBuf>,
+}
+
+// Styles in the dotted format console understands, e.g. bold.green or on_black.italic
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields, default)]
+pub struct Styles {
+    // Progress messages
+    pub prefix: String,
+    pub success: String,
+    pub fail: String,
+    // list output
+    pub label: String,
+    pub version: String,
+    pub url: String,
+    pub changelog: String,
+    pub header: String,
+    pub directory: String,
+    pub status: String,
+}
+
+impl Default for Styles {
+    fn default() -> Self {
+        Self {
+            prefix: String::from("white.bold"),
+            success: String::from("italic.bold.green"),
+            fail: String::from("italic.bold.red"),
+            label: String::from("bold"),
+            version: String::from("green"),
+            url: String::from("blue.underlined"),
+            changelog: String::from("dim"),
+            header: String::from("bold.underlined"),
+            directory: String::from("blue"),
+            status: String::from("dim"),
+        }
+    }
+}
+
+// GitHub API client settings. PROTONCTL_API_URL and GITHUB_TOKEN take precedence over these.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Api {
+    pub url: Option<String>,
+    pub token: Option<String>,
+    // Seconds
+    pub timeout: Option<u64>,
+    pub connect_timeout: Option<u64>,
+    pub retries: Option<u32>,
+    pub proxy: Option<String>,
+    pub ca_certificate: Option<PathBuf>,
+}
+
+impl Config {
+    // PROTONCTL_CONFIG if it's set, otherwise config.toml in the XDG config directory
+    pub fn path() -> Result<PathBuf> {
+        if let Some(path) = std::env::var_os(CONFIG_ENV).filter(|p| !p.is_empty()) {
+            return Ok(PathBuf::from(path));
+        }
+        let mut path = dirs::config_dir().ok_or(Error::HomeDirMissing)?;
+        path.push(constants::CONFIG_DIRECTORY);
+        path.push(constants::CONFIG_FILE);
+        Ok(path)
+    }
+
+    // Load the config, treating a missing file as the default config
+    pub fn load() -> Result<Self> {
+        Self::load_from(&Self::path()?)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| Error::io(format!("Failed to read config: {:?}", path), e))?;
+        Self::parse(&contents).map_err(|e| match e {
+            Error::Config(reason) => Error::Config(format!("{:?}: {}", path, reason)),
+            e => e,
+        })
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let config: Self = toml::from_str(contents).map_err(|e| Error::Config(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for (i, source) in self.sources.iter().enumerate() {
+            source.validate()?;
+            if InstallType::from_name(&source.name).is_some() {
+                return Err(Error::Config(format!(
+                    "source {:?} has the same name as a built in install type",
+                    source.name
+                )));
+            }
+            if self.sources[..i].iter().any(|s| s.name == source.name) {
+                return Err(Error::Config(format!(
+                    "source {:?} is defined more than once",
+                    source.name
+                )));
+            }
+        }
+        let install_type = &self.defaults.install_type;
+        if InstallType::from_name(install_type).is_none() && self.source(install_type).is_none() {
+            return Err(Error::Config(format!(
+                "defaults.type {:?} isn't proton, wine, ulwgl or the name of a source",
+                install_type
+            )));
+        }
+        if self.defaults.number == 0 || self.defaults.number > constants::MAX_PER_PAGE {
+            return Err(Error::Config(format!(
+                "defaults.number must be between 1 and {}",
+                constants::MAX_PER_PAGE
+            )));
+        }
+        for (key, path) in self.paths.iter() {
+            if path.is_some_and(|p| p.as_os_str().is_empty()) {
+                return Err(Error::Config(format!("paths.{} can't be empty", key)));
+            }
+        }
+        for (key, style) in self.styles.iter() {
+            if let Some(word) = style.split('.').find(|word| !is_style_word(word)) {
+                return Err(Error::Config(format!(
+                    "styles.{} has an unknown style {:?}. Expected dotted colours and attributes such as bold.green",
+                    key, word
+                )));
+            }
+        }
+        if let Some(url) = &self.api.url {
+            if !(url.starts_with("http://") || url.starts_with("https://")) {
+                return Err(Error::Config(format!(
+                    "api.url {:?} must start with http:// or https://",
+                    url
+                )));
+            }
+        }
+        if self.api.timeout == Some(0) || self.api.connect_timeout == Some(0) {
+            return Err(Error::Config(String::from(
+                "api timeouts must be at least 1 second",
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn source(&self, name: &str) -> Option<&CustomSource> {
+        self.sources.iter().find(|s| s.name == name)
+    }
+
+    // Where downloads go, without creating it as dry runs need
+    pub fn download_path(&self) -> Result<PathBuf> {
+        match &self.paths.download {
+            Some(path) => utils::home_path(path),
+            None => Ok(dirs::cache_dir()
+                .ok_or(Error::HomeDirMissing)?
+                .join(constants::DATA_DIRECTORY)),
+        }
+    }
+
+    // Where the manifest is kept, without creating it
+    pub fn data_path(&self) -> Result<PathBuf> {
+        match &self.paths.data {
+            Some(path) => utils::home_path(path),
+            None => Ok(dirs::data_dir()
+                .ok_or(Error::HomeDirMissing)?
+                .join(constants::DATA_DIRECTORY)),
+        }
+    }
+
+    // The download directory, created if it doesn't exist
+    pub fn download_directory(&self) -> Result<PathBuf> {
+        utils::create_directory_safe(&self.download_path()?)
+    }
+
+    // The directory the manifest is kept in, created if it doesn't exist. A manifest left in
+    // the old location is moved over the first time.
+    pub fn data_directory(&self) -> Result<PathBuf> {
+        let directory = utils::create_directory_safe(&self.data_path()?)?;
+        let manifest = directory.join(constants::MANIFEST_FILE);
+        let legacy = utils::home_path(Path::new(constants::LEGACY_DATA_PATH))?
+            .join(constants::MANIFEST_FILE);
+        if !manifest.exists() && legacy.exists() {
+            std::fs::rename(&legacy, &manifest)
+                .map_err(|e| Error::io(format!("Failed to move manifest {:?}", legacy), e))?;
+        }
+        Ok(directory)
+    }
+
+    // The compatibility directory of a built in install type
+    pub fn compat_directory(&self, install_type: InstallType, target: Target) -> Result<PathBuf> {
+        let configured = match (install_type, target) {
+            (InstallType::Proton, Target::Native) => &self.paths.proton,
+            (InstallType::Proton, Target::Flatpak) => &self.paths.proton_flatpak,
+            (InstallType::Proton, Target::Snap) => &self.paths.proton_snap,
+            (InstallType::Wine, Target::Native) => &self.paths.wine,
+            (InstallType::Wine, Target::Flatpak) => &self.paths.wine_flatpak,
+            (InstallType::ULWGL, Target::Native) => &self.paths.ulwgl,
+            _ => &None,
+        };
+        match configured {
+            Some(path) => utils::home_path(path),
+            None => install_type.default_compat_directory(target),
+        }
+    }
+
+    // A client for the GitHub API using the api settings. The environment wins over the file.
+    pub fn client(&self) -> Result<Client> {
+        let mut builder = Client::builder();
+        if std::env::var_os(api::BASE_URL_ENV).is_none() {
+            if let Some(url) = &self.api.url {
+                builder = builder.base_url(url);
+            }
+        }
+        if api::token().is_none() {
+            builder = builder.token(self.api.token.clone());
+        }
+        if let Some(timeout) = self.api.timeout {
+            builder = builder.timeout(Duration::from_secs(timeout));
+        }
+        if let Some(timeout) = self.api.connect_timeout {
+            builder = builder.connect_timeout(Duration::from_secs(timeout));
+        }
+        if let Some(retries) = self.api.retries {
+            builder = builder.retries(retries, Duration::from_secs(1));
+        }
+        if let Some(proxy) = &self.api.proxy {
+            builder = builder.proxy(proxy);
+        }
+        if let Some(path) = &self.api.ca_certificate {
+            builder = builder.ca_certificate(utils::home_path(path)?);
+        }
+        builder.build()
+    }
+
+    // The config with unset paths filled in with the directories that would be used, for
+    // config show
+    pub fn effective(&self) -> Result<Self> {
+        let mut config = self.clone();
+        config.paths = Paths {
+            download: Some(self.download_path()?),
+            data: Some(self.data_path()?),
+            proton: Some(self.compat_directory(InstallType::Proton, Target::Native)?),
+            proton_flatpak: Some(self.compat_directory(InstallType::Proton, Target::Flatpak)?),
+            proton_snap: Some(self.compat_directory(InstallType::Proton, Target::Snap)?),
+            wine: Some(self.compat_directory(InstallType::Wine, Target::Native)?),
+            wine_flatpak: Some(self.compat_directory(InstallType::Wine, Target::Flatpak)?),
+            ulwgl: Some(self.compat_directory(InstallType::ULWGL, Target::Native)?),
+        };
+        Ok(config)
+    }
+
+    // The effective config as TOML for config show, with the token hidden so it doesn't end up
+    // in terminal scrollback or bug reports
+    pub fn show(&self) -> Result<String> {
+        toml::to_string_pretty(&self.shown()?).map_err(|e| Error::Config(e.to_string()))
+    }
+
+    // Look up a dotted key. Returns None for keys that are unset. The token is hidden like in
+    // show.
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        check_key(key)?;
+        let value =
+            toml::Value::try_from(self.shown()?).map_err(|e| Error::Config(e.to_string()))?;
+        Ok(key
+            .split('.')
+            .try_fold(&value, |value, part| value.get(part))
+            .map(|value| match value {
+                toml::Value::String(s) => s.clone(),
+                value => value.to_string(),
+            }))
+    }
+
+    // The effective config with the token hidden
+    fn shown(&self) -> Result<Self> {
+        let mut config = self.effective()?;
+        if config.api.token.is_some() {
+            config.api.token = Some(String::from("<hidden>"));
+        }
+        Ok(config)
+    }
+
+    // Set a dotted key in the config file at path, keeping everything else in the file,
+    // comments and layout included, as is. The file is only written if the result is a valid
+    // config.
+    pub fn set(path: &Path, key: &str, value: &str) -> Result<Self> {
+        let (config, contents) = Self::with_value(path, key, value)?;
+        if let Some(parent) = path.parent() {
+            utils::create_directory_safe(parent)?;
+        }
+        utils::write_atomic(path, &contents)?;
+        Ok(config)
+    }
+
+    // The checked config and file contents set would write, without writing them
+    pub fn with_value(path: &Path, key: &str, value: &str) -> Result<(Self, String)> {
+        check_key(key)?;
+        // Edited as a document rather than a table so comments and layout survive
+        let mut document: toml_edit::DocumentMut = if path.exists() {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|e| Error::io(format!("Failed to read config: {:?}", path), e))?;
+            contents
+                .parse()
+                .map_err(|e| Error::Config(format!("{:?}: {}", path, e)))?
+        } else {
+            toml_edit::DocumentMut::new()
+        };
+        let value = if INTEGER_KEYS.contains(&key) {
+            toml_edit::value(value.parse::<i64>().map_err(|_| {
+                Error::Config(format!("{} must be a whole number, not {:?}", key, value))
+            })?)
+        } else {
+            toml_edit::value(value)
+        };
+        let (section, name) = key.split_once('.').unwrap_or(("", key));
+        let section = document
+            .entry(section)
+            .or_insert(toml_edit::table())
+            .as_table_like_mut()
+            .ok_or(Error::Config(format!("{} isn't a table", section)))?;
+        section.insert(name, value);
+
+        let contents = document.to_string();
+        Ok((Self::parse(&contents)?, contents))
+    }
+}
+
+impl Paths {
+    fn iter(&self) -> impl Iterator<Item = (&str, Option<&PathBuf>)> {
+        [
+            ("download", self.download.as_ref()),
+            ("data", self.data.as_ref()),
+            ("proton", self.proton.as_ref()),
+            ("proton_flatpak", self.proton_flatpak.as_ref()),
+            ("proton_snap", self.proton_snap.as_ref()),
+            ("wine", self.wine.as_ref()),
+            ("wine_flatpak", self.wine_flatpak.as_ref()),
+            ("ulwgl", self.ulwgl.as_ref()),
+        ]
+        .into_iter()
+    }
+}
+
+impl Styles {
+    fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        [
+            ("prefix", self.prefix.as_str()),
+            ("success", self.success.as_str()),
+            ("fail", self.fail.as_str()),
+            ("label", self.label.as_str()),
+            ("version", self.version.as_str()),
+            ("url", self.url.as_str()),
+            ("changelog", self.changelog.as_str()),
+            ("header", self.header.as_str()),
+            ("directory", self.directory.as_str()),
+            ("status", self.status.as_str()),
+        ]
+        .into_iter()
+    }
+}
+
+fn check_key(key: &str) -> Result<()> {
+    if KEYS.contains(&key) {
+        return Ok(());
+    }
+    let hint = if key == "sources" || key.starts_with("sources.") {
+        String::from(". Sources have to be edited in the config file")
+    } else {
+        format!(". Expected one of: {}", KEYS.join(", "))
+    };
+    Err(Error::Config(format!("Unknown key {:?}{}", key, hint)))
+}
+
+// Words console::Style::from_dotted_str understands. An empty style is allowed.
+fn is_style_word(word: &str) -> bool {
+    const COLOURS: &[&str] = &[
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    ];
+    const ATTRIBUTES: &[&str] = &[
+        "bold",
+        "dim",
+        "italic",
+        "underlined",
+        "blink",
+        "blink_fast",
+        "reverse",
+        "hidden",
+        "strikethrough",
+        "bright",
+        "on_bright",
+    ];
+    let colour = word.strip_prefix("on_").unwrap_or(word);
+    word.is_empty()
+        || ATTRIBUTES.contains(&word)
+        || COLOURS.contains(&colour)
+        || colour.parse::<u8>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::test_utils::TempDir;
+
+    const KRON4EK: &str = r#"
+[[sources]]
+name = "kron4ek"
+owner = "Kron4ek"
+repo = "Wine-Builds"
+asset_pattern = "wine-*-staging-tkg-amd64.tar.xz"
+directory = ".local/share/lutris/runners/wine"
+flatpak_directory = ".var/app/net.lutris.Lutris/data/lutris/runners/wine"
+"#;
+
+    #[test]
+    fn can_parse_sources() {
+        let config = Config::parse(KRON4EK).unwrap();
+        let source = config.source("kron4ek").unwrap();
+        assert_eq!(source.repository(), "Kron4ek/Wine-Builds");
+        assert!(source.checksum_pattern.is_none());
+        assert!(Config::parse("").unwrap().sources.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_sources() {
+        let builtin = KRON4EK.replace("\"kron4ek\"", "\"wine\"");
+        assert!(Config::parse(&builtin).is_err());
+        let duplicate = format!("{}{}", KRON4EK, KRON4EK);
+        assert!(Config::parse(&duplicate).is_err());
+        let no_repo = KRON4EK.replace("repo = \"Wine-Builds\"", "repo = \"\"");
+        assert!(Config::parse(&no_repo).is_err());
+        let missing_field = KRON4EK.replace("asset_pattern", "pattern");
+        assert!(Config::parse(&missing_field).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        for contents in [
+            "[defaults]\ntype = \"kron4ek\"",
+            "[defaults]\nnumber = 0",
+            "[defaults]\nnumber = 51",
+            "[styles]\nprefix = \"bold.purple\"",
+            "[api]\nurl = \"api.github.com\"",
+            "[paths]\nproton = \"\"",
+            "[paths]\nsteam = \"~/.steam\"",
+        ] {
+            assert!(Config::parse(contents).is_err(), "{}", contents);
+        }
+        let config = format!("[defaults]\ntype = \"kron4ek\"\n{}", KRON4EK);
+        assert!(Config::parse(&config).is_ok());
+        assert!(Config::parse("[styles]\nheader = \"on_black.bright.white.196\"").is_ok());
+    }
+
+    #[test]
+    fn can_get_and_set_keys() -> std::io::Result<()> {
+        let dir = TempDir::new("config");
+        let path = dir.join("config.toml");
+
+        let config = Config::set(&path, "defaults.number", "25").unwrap();
+        assert_eq!(config.defaults.number, 25);
+        Config::set(&path, "paths.proton", "/games/proton").unwrap();
+        assert!(Config::set(&path, "defaults.number", "many").is_err());
+        assert!(Config::set(&path, "styles.url", "sparkly").is_err());
+        assert!(Config::set(&path, "paths.steam", "/games").is_err());
+
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.get("defaults.number").unwrap().unwrap(), "25");
+        assert_eq!(
+            config.get("paths.proton").unwrap().unwrap(),
+            "/games/proton"
+        );
+        assert_eq!(config.get("defaults.type").unwrap().unwrap(), "proton");
+        assert!(config.get("api.token").unwrap().is_none());
+
+        Config::set(&path, "api.token", "secret").unwrap();
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.api.token.as_deref(), Some("secret"));
+        assert_eq!(config.get("api.token").unwrap().unwrap(), "<hidden>");
+        Ok(())
+    }
+
+    #[test]
+    fn set_keeps_comments() {
+        let dir = TempDir::new("config-comments");
+        let path = dir.join("config.toml");
+        let contents = "# Where builds go\n[paths]\nproton = \"/games/proton\" # fast disk\n";
+        std::fs::write(&path, contents).unwrap();
+
+        Config::set(&path, "paths.wine", "/games/wine").unwrap();
+        Config::set(&path, "api.retries", "2").unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with(contents), "{}", written);
+        assert!(written.contains("wine = \"/games/wine\""));
+        assert!(written.contains("[api]\nretries = 2"));
+    }
+}
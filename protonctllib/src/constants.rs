@@ -10,4 +10,25 @@ pub const PROTON_PROJECT_NAME: &str = "proton-ge-custom";
 
 pub const ULWGL_PROJECT_NAME: &str = "ULWGL-Proton";
 
-pub const DOWNLOAD_PATH: &str = ".local/share/protonctl";
+// Name of the directories protonctl keeps downloads (XDG cache) and the manifest (XDG data) in
+pub const DATA_DIRECTORY: &str = "protonctl";
+
+// Where downloads and the manifest were kept before the XDG directories were used
+pub const LEGACY_DATA_PATH: &str = ".local/share/protonctl";
+
+pub const STEAM_FLATPAK_ID: &str = "com.valvesoftware.Steam";
+
+pub const LUTRIS_FLATPAK_ID: &str = "net.lutris.Lutris";
+pub const HEROIC_FLATPAK_ID: &str = "com.heroicgameslauncher.hgl";
+pub const BOTTLES_FLATPAK_ID: &str = "com.usebottles.bottles";
+
+// Relative to the home directory
+pub const STEAM_SNAP_PATH: &str = "snap/steam";
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+pub const STAGING_DIRECTORY: &str = ".protonctl-staging";
+
+pub const CONFIG_DIRECTORY: &str = "protonctl";
+
+pub const CONFIG_FILE: &str = "config.toml";
@@ -1,10 +1,14 @@
-use anyhow::{Context, Result};
+use crate::constants;
+use crate::error::{Error, Result};
+use crate::install_type::InstallType;
 use flate2::read::GzDecoder;
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use tar::Archive;
 use xz2::read::XzDecoder;
 
-pub fn decompress(compressed: &PathBuf, out: &PathBuf) -> Result<()> {
+// Decompress into out and return the top level entries of the archive
+pub fn decompress(compressed: &PathBuf, out: &PathBuf) -> Result<Vec<String>> {
     let extension = compressed.extension();
     match extension {
         Some(e) => {
@@ -13,31 +17,296 @@ pub fn decompress(compressed: &PathBuf, out: &PathBuf) -> Result<()> {
             } else if e == "xz" {
                 lzma(compressed, out)
             } else {
-                Err(anyhow::anyhow!("Unknown extension: {:?}", extension))
+                Err(Error::UnknownArchive(e.to_string_lossy().into_owned()))
             }
         }
-        None => Err(anyhow::anyhow!("Failed to get the file extension")),
+        None => Err(Error::UnknownArchive(
+            compressed.to_string_lossy().into_owned(),
+        )),
     }
 }
 
-fn gunzip(compressed: &PathBuf, out: &PathBuf) -> Result<()> {
+// Decompress into a staging directory inside out, check the result looks like a release of
+// install_type and only then rename it into place, under rename if given. Releases from custom
+// sources (install_type None) only need a single top level directory. Returns the name of the
+// installed directory.
+pub fn install_archive(
+    compressed: &PathBuf,
+    out: &Path,
+    install_type: Option<InstallType>,
+    rename: Option<&str>,
+) -> Result<String> {
+    let name = compressed.file_name().ok_or(Error::UnknownArchive(
+        compressed.to_string_lossy().into_owned(),
+    ))?;
+    with_staging(out, name, |staging| {
+        stage_and_rename(compressed, out, staging, install_type, rename)
+    })
+}
+
+// Install another copy of an installed release into out by hard linking its files, so one
+// download can go to several compatibility directories without taking up the space twice.
+// Fails when out is on a different filesystem. Returns the name of the installed directory.
+pub fn link_install(installed: &Path, out: &Path, rename: Option<&str>) -> Result<String> {
+    let name = installed.file_name().ok_or(Error::InvalidRelease(format!(
+        "{:?} has no name",
+        installed
+    )))?;
+    with_staging(out, name, |staging| {
+        let staged = staging.join(name);
+        link_tree(installed, &staged)?;
+        let name = match rename {
+            Some(rename) => rename.to_string(),
+            None => name.to_string_lossy().into_owned(),
+        };
+        move_into_place(&staged, out, staging, &name)?;
+        Ok(name)
+    })
+}
+
+// Run install with a clean staging directory inside out, removing it again afterwards
+fn with_staging<T>(
+    out: &Path,
+    name: &std::ffi::OsStr,
+    install: impl FnOnce(&PathBuf) -> Result<T>,
+) -> Result<T> {
+    let mut staging = out.join(constants::STAGING_DIRECTORY);
+    staging.push(name);
+    // Anything left here is from an install that was interrupted
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)
+            .map_err(|e| Error::io("Failed to clean staging directory", e))?;
+    }
+    std::fs::create_dir_all(&staging)
+        .map_err(|e| Error::io("Failed to create staging directory", e))?;
+
+    let result = install(&staging);
+    if let Err(e) = std::fs::remove_dir_all(&staging) {
+        eprintln!("Failed to remove staging directory {:?}: {}", staging, e);
+    }
+    // Only succeeds when no other install is using the staging area
+    let _ = std::fs::remove_dir(staging.parent().unwrap_or(out));
+    result
+}
+
+// Recreate the directory tree at from in to, hard linking files and copying symlinks
+fn link_tree(from: &Path, to: &Path) -> Result<()> {
+    let metadata =
+        std::fs::metadata(from).map_err(|e| Error::io(format!("Failed to read {:?}", from), e))?;
+    std::fs::create_dir(to).map_err(|e| Error::io(format!("Failed to create {:?}", to), e))?;
+    std::fs::set_permissions(to, metadata.permissions())
+        .map_err(|e| Error::io(format!("Failed to set permissions of {:?}", to), e))?;
+    let entries =
+        std::fs::read_dir(from).map_err(|e| Error::io(format!("Failed to read {:?}", from), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| Error::io(format!("Failed to read {:?}", from), e))?;
+        let file_type = entry
+            .file_type()
+            .map_err(|e| Error::io(format!("Failed to read {:?}", entry.path()), e))?;
+        let destination = to.join(entry.file_name());
+        if file_type.is_dir() {
+            link_tree(&entry.path(), &destination)?;
+        } else if file_type.is_symlink() {
+            let link = std::fs::read_link(entry.path())
+                .map_err(|e| Error::io(format!("Failed to read {:?}", entry.path()), e))?;
+            std::os::unix::fs::symlink(link, &destination)
+                .map_err(|e| Error::io(format!("Failed to create {:?}", destination), e))?;
+        } else {
+            std::fs::hard_link(entry.path(), &destination)
+                .map_err(|e| Error::io(format!("Failed to link {:?}", destination), e))?;
+        }
+    }
+    Ok(())
+}
+
+fn stage_and_rename(
+    compressed: &PathBuf,
+    out: &Path,
+    staging: &PathBuf,
+    install_type: Option<InstallType>,
+    rename: Option<&str>,
+) -> Result<String> {
+    let top_level = decompress(compressed, staging)?;
+    let name = match top_level.as_slice() {
+        [name] => name.clone(),
+        _ => {
+            return Err(Error::InvalidRelease(format!(
+                "Expected a single top level directory but found: {:?}",
+                top_level
+            )))
+        }
+    };
+    let mut staged = staging.clone();
+    staged.push(&name);
+    if !staged.is_dir() {
+        return Err(Error::InvalidRelease(format!(
+            "{:?} is not a directory",
+            name
+        )));
+    }
+    if let Some(install_type) = install_type {
+        for required in install_type.required_files() {
+            if !staged.join(required).exists() {
+                return Err(Error::InvalidRelease(format!(
+                    "{:?} is missing {:?}. Is this a {} release?",
+                    name, required, install_type
+                )));
+            }
+        }
+    }
+
+    let name = rename.map(String::from).unwrap_or(name);
+    move_into_place(&staged, out, staging, &name)?;
+    Ok(name)
+}
+
+// Move a previous install of the same release aside so the rename can't fail half way.
+// It's deleted along with the staging directory.
+fn move_into_place(staged: &Path, out: &Path, staging: &Path, name: &str) -> Result<()> {
+    let destination = out.join(name);
+    let previous = staging.join(format!("{}.previous", name));
+    let replacing = destination.exists();
+    if replacing {
+        std::fs::rename(&destination, &previous)
+            .map_err(|e| Error::io(format!("Failed to move aside {:?}", destination), e))?;
+    }
+    if let Err(e) = std::fs::rename(staged, &destination) {
+        if replacing {
+            let _ = std::fs::rename(&previous, &destination);
+        }
+        return Err(Error::io(
+            format!("Failed to move {:?} into place", name),
+            e,
+        ));
+    }
+    Ok(())
+}
+
+fn gunzip(compressed: &PathBuf, out: &PathBuf) -> Result<Vec<String>> {
     let file = std::fs::OpenOptions::new()
         .read(true)
         .open(compressed)
-        .context("Failed to open compressed file for reading")?;
-    let mut archive = Archive::new(GzDecoder::new(file));
-    archive
-        .unpack(out)
-        .context("Failed to unpack gunzip file")?;
-    Ok(())
+        .map_err(|e| Error::io("Failed to open compressed file for reading", e))?;
+    unpack(Archive::new(GzDecoder::new(file)), out)
+        .map_err(|e| Error::extraction("Failed to unpack gunzip file", e))
 }
 
-fn lzma(compressed: &PathBuf, out: &PathBuf) -> Result<()> {
+fn lzma(compressed: &PathBuf, out: &PathBuf) -> Result<Vec<String>> {
     let file = std::fs::OpenOptions::new()
         .read(true)
         .open(compressed)
-        .context("Failed to open compressed file for reading")?;
-    let mut archive = Archive::new(XzDecoder::new(file));
-    archive.unpack(out).context("Failed to unpack xz file")?;
-    Ok(())
+        .map_err(|e| Error::io("Failed to open compressed file for reading", e))?;
+    unpack(Archive::new(XzDecoder::new(file)), out)
+        .map_err(|e| Error::extraction("Failed to unpack xz file", e))
+}
+
+fn unpack<R: Read>(mut archive: Archive<R>, out: &PathBuf) -> std::io::Result<Vec<String>> {
+    let mut top_level: Vec<String> = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if let Some(std::path::Component::Normal(first)) = path
+            .components()
+            .find(|c| !matches!(c, std::path::Component::CurDir))
+        {
+            let first = first.to_string_lossy().into_owned();
+            if !top_level.contains(&first) {
+                top_level.push(first);
+            }
+        }
+        entry.unpack_in(out)?;
+    }
+    Ok(top_level)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decompress::{install_archive, link_install};
+    use crate::install_type::InstallType;
+    use crate::test_utils::TempDir;
+    use flate2::{write::GzEncoder, Compression};
+    use std::os::unix::fs::MetadataExt;
+
+    // Build a tar.gz containing the given files and return the directory it was written to
+    fn archive_with(test: &str, files: &[&str]) -> (TempDir, std::path::PathBuf) {
+        let dir = TempDir::new(test);
+        std::fs::create_dir_all(dir.join("out")).unwrap();
+        let tar_path = dir.join("GE-Proton8-25.tar.gz");
+        let file = std::fs::File::create(&tar_path).unwrap();
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::fast()));
+        for name in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(0);
+            header.set_mode(0o755);
+            header.set_cksum();
+            builder.append_data(&mut header, name, &[][..]).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+        (dir, tar_path)
+    }
+
+    #[test]
+    fn can_install_archive() -> std::io::Result<()> {
+        let (dir, tar_path) = archive_with(
+            "install",
+            &[
+                "GE-Proton8-25/proton",
+                "GE-Proton8-25/compatibilitytool.vdf",
+            ],
+        );
+        let out = dir.join("out");
+        let name = install_archive(&tar_path, &out, Some(InstallType::Proton), None).unwrap();
+        assert_eq!(name, "GE-Proton8-25");
+        assert!(out.join("GE-Proton8-25/proton").exists());
+        assert!(!out.join(crate::constants::STAGING_DIRECTORY).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_archive_is_not_installed() -> std::io::Result<()> {
+        let (dir, tar_path) = archive_with("invalid", &["GE-Proton8-25/proton"]);
+        let out = dir.join("out");
+        assert!(install_archive(&tar_path, &out, Some(InstallType::Proton), None).is_err());
+        assert_eq!(std::fs::read_dir(&out)?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn can_link_installs() -> std::io::Result<()> {
+        let (dir, tar_path) = archive_with(
+            "link",
+            &[
+                "GE-Proton8-25/proton",
+                "GE-Proton8-25/compatibilitytool.vdf",
+                "GE-Proton8-25/files/bin/wine",
+            ],
+        );
+        let out = dir.join("out");
+        let other = dir.join("other");
+        std::fs::create_dir_all(&other)?;
+        install_archive(&tar_path, &out, Some(InstallType::Proton), None).unwrap();
+        std::os::unix::fs::symlink("files/bin/wine", out.join("GE-Proton8-25/wine"))?;
+
+        let name = link_install(&out.join("GE-Proton8-25"), &other, None).unwrap();
+        assert_eq!(name, "GE-Proton8-25");
+        let linked = other.join("GE-Proton8-25");
+        assert_eq!(std::fs::metadata(linked.join("files/bin/wine"))?.nlink(), 2);
+        assert_eq!(
+            std::fs::read_link(linked.join("wine"))?,
+            std::path::PathBuf::from("files/bin/wine")
+        );
+        assert!(!other.join(crate::constants::STAGING_DIRECTORY).exists());
+
+        // Bottles names its runners itself
+        let bottles = dir.join("bottles");
+        let name = link_install(
+            &out.join("GE-Proton8-25"),
+            &bottles,
+            Some("wine-ge-proton8-25"),
+        )
+        .unwrap();
+        assert_eq!(name, "wine-ge-proton8-25");
+        assert!(bottles.join("wine-ge-proton8-25/proton").exists());
+        Ok(())
+    }
 }
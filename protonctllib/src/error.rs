@@ -0,0 +1,178 @@
+// Errors returned by the public functions of protonctllib
+use crate::target::Target;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug)]
+pub enum Error {
+    // Network
+    Network(reqwest::Error),
+    // API
+    NotFound(String),
+    RateLimited {
+        // Seconds since the unix epoch
+        reset: Option<u64>,
+        authenticated: bool,
+    },
+    Api {
+        status: u16,
+        message: String,
+    },
+    // Download
+    DownloadStatus(u16),
+    DownloadInterrupted(reqwest::Error),
+    // Verification
+    HashMismatch {
+        expected: String,
+        actual: String,
+    },
+    InvalidChecksum(String),
+    SizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
+    // Extraction
+    UnknownArchive(String),
+    Extraction {
+        context: String,
+        source: std::io::Error,
+    },
+    InvalidRelease(String),
+    // Filesystem
+    HomeDirMissing,
+    CompatDirMissing(PathBuf),
+    Io {
+        context: String,
+        source: std::io::Error,
+    },
+    Manifest {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    Vdf(String),
+    Yaml {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+    Database {
+        path: PathBuf,
+        source: rusqlite::Error,
+    },
+    // Configuration
+    Config(String),
+    // Usage
+    AmbiguousTarget(Vec<Target>),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn io(context: impl Into<String>, source: std::io::Error) -> Self {
+        Error::Io {
+            context: context.into(),
+            source,
+        }
+    }
+
+    pub fn extraction(context: impl Into<String>, source: std::io::Error) -> Self {
+        Error::Extraction {
+            context: context.into(),
+            source,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Network(_) => write!(f, "Network request failed"),
+            Error::NotFound(what) => write!(f, "Not found: {}", what),
+            Error::RateLimited {
+                reset,
+                authenticated,
+            } => {
+                write!(f, "GitHub API rate limit exceeded")?;
+                if let Some(reset) = reset {
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or_default();
+                    let minutes = reset.saturating_sub(now).div_ceil(60);
+                    write!(
+                        f,
+                        ". It resets in {} minute{} (unix time {})",
+                        minutes,
+                        if minutes == 1 { "" } else { "s" },
+                        reset
+                    )?;
+                }
+                if !authenticated {
+                    write!(f, ". Set GITHUB_TOKEN or GH_TOKEN to raise the limit")?;
+                }
+                Ok(())
+            }
+            Error::Api { status, message } => {
+                write!(f, "GitHub API error ({}): {}", status, message)
+            }
+            Error::DownloadStatus(status) => {
+                write!(f, "Download failed, server responded with {}", status)
+            }
+            Error::DownloadInterrupted(_) => write!(f, "Download interrupted"),
+            Error::HashMismatch { expected, actual } => write!(
+                f,
+                "Hash mismatch error! Expected {} but got {}",
+                expected, actual
+            ),
+            Error::InvalidChecksum(sha) => write!(f, "Invalid sha512sum: {:?}", sha),
+            Error::SizeMismatch { expected, actual } => {
+                write!(f, "Downloaded {} bytes but expected {}", actual, expected)
+            }
+            Error::UnknownArchive(extension) => write!(f, "Unknown extension: {}", extension),
+            Error::Extraction { context, .. } => write!(f, "{}", context),
+            Error::InvalidRelease(reason) => write!(f, "Invalid release archive: {}", reason),
+            Error::HomeDirMissing => write!(f, "Couldn't get users home directory"),
+            Error::CompatDirMissing(path) => write!(
+                f,
+                "Failed to read compatibility directory {:?}. Does it exist?",
+                path
+            ),
+            Error::Io { context, .. } => write!(f, "{}", context),
+            Error::Manifest { path, .. } => write!(f, "Failed to parse manifest: {:?}", path),
+            Error::Vdf(reason) => write!(f, "Failed to parse VDF file: {}", reason),
+            Error::Yaml { path, .. } => write!(f, "Failed to parse YAML file: {:?}", path),
+            Error::Database { path, .. } => write!(f, "Failed to read database: {:?}", path),
+            Error::Config(reason) => write!(f, "Invalid configuration: {}", reason),
+            Error::AmbiguousTarget(detected) => write!(
+                f,
+                "Found more than one install ({}). Choose one with --target or use --target all",
+                detected
+                    .iter()
+                    .map(|t| t.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Network(e) => Some(e),
+            Error::DownloadInterrupted(e) => Some(e),
+            Error::Extraction { source, .. } => Some(source),
+            Error::Io { source, .. } => Some(source),
+            Error::Manifest { source, .. } => Some(source),
+            Error::Yaml { source, .. } => Some(source),
+            Error::Database { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Network(e)
+    }
+}
@@ -1,8 +1,11 @@
 // Structs/Helpers for the github releases api
 
 pub mod api {
+    use crate::error::{Error, Result};
     use reqwest;
     use serde::Deserialize;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
     #[derive(Deserialize, Debug, Clone, Default)]
     pub struct AssetId {
@@ -41,49 +44,370 @@ pub mod api {
         pub tag_name: String,
         pub assets: Vec<AssetId>,
         pub body: String,
+        #[serde(default)]
+        pub prerelease: bool,
+        #[serde(default)]
+        pub draft: bool,
     }
 
     pub type Releases = Vec<Release>;
 
-    pub async fn releases(
-        url: &str,
-        per_page: Option<u8>,
-        page: Option<u8>,
-    ) -> Result<Releases, reqwest::Error> {
-        let pp: u8 = per_page.unwrap_or(10);
-        let p: u8 = page.unwrap_or(1);
-
-        reqwest::Client::new()
-            .get(url)
-            .query(&[("per_page", pp), ("page", p)])
-            .header("user-agent", "protonctl-rs")
-            .send()
-            .await?
-            .json::<Releases>()
-            .await
+    pub const DEFAULT_BASE_URL: &str = "https://api.github.com";
+
+    // Environment variable that overrides DEFAULT_BASE_URL, e.g. to point at a mirror or a mock
+    // server in tests
+    pub const BASE_URL_ENV: &str = "PROTONCTL_API_URL";
+
+    pub fn base_url() -> String {
+        std::env::var(BASE_URL_ENV)
+            .ok()
+            .filter(|url| !url.is_empty())
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| DEFAULT_BASE_URL.to_string())
     }
 
-    pub async fn latest_release(url: &str) -> Result<Release, reqwest::Error> {
-        reqwest::Client::new()
-            .get(url)
-            .header("user-agent", "protonctl-rs")
-            .send()
-            .await?
-            .json::<Release>()
-            .await
-    }
-
-    pub async fn release_version(url: &str, version: &str) -> Result<Release, reqwest::Error> {
-        let mut url = url.to_owned();
-        url.push_str("/tags/");
-        url.push_str(version);
-        reqwest::Client::new()
-            .get(url)
-            .header("user-agent", "protonctl-rs")
-            .send()
-            .await?
-            .json::<Release>()
-            .await
+    // Token used to authenticate against the GitHub API, taken from GITHUB_TOKEN or GH_TOKEN
+    pub fn token() -> Option<String> {
+        ["GITHUB_TOKEN", "GH_TOKEN"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|token| !token.is_empty())
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct RateLimit {
+        pub limit: u64,
+        pub remaining: u64,
+        // Seconds since the unix epoch
+        pub reset: u64,
+    }
+
+    impl RateLimit {
+        pub fn from_headers(headers: &reqwest::header::HeaderMap) -> Option<Self> {
+            let header =
+                |name: &str| -> Option<u64> { headers.get(name)?.to_str().ok()?.parse().ok() };
+            Some(Self {
+                limit: header("x-ratelimit-limit")?,
+                remaining: header("x-ratelimit-remaining")?,
+                reset: header("x-ratelimit-reset")?,
+            })
+        }
+    }
+
+    // Builder for Client. Defaults to api.github.com (or PROTONCTL_API_URL) with the token from
+    // the environment, no timeouts, the system proxy settings and two retries.
+    #[derive(Debug, Clone)]
+    pub struct ClientBuilder {
+        base_url: String,
+        token: Option<String>,
+        timeout: Option<Duration>,
+        connect_timeout: Option<Duration>,
+        retries: u32,
+        retry_delay: Duration,
+        proxy: Option<String>,
+        ca_certificate: Option<PathBuf>,
+        headers: Vec<(String, String)>,
+    }
+
+    impl Default for ClientBuilder {
+        fn default() -> Self {
+            Self {
+                base_url: base_url(),
+                token: token(),
+                timeout: None,
+                connect_timeout: None,
+                retries: 2,
+                retry_delay: Duration::from_secs(1),
+                proxy: None,
+                ca_certificate: None,
+                headers: Vec::new(),
+            }
+        }
+    }
+
+    impl ClientBuilder {
+        pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+            self.base_url = base_url.into().trim_end_matches('/').to_string();
+            self
+        }
+
+        pub fn token(mut self, token: Option<String>) -> Self {
+            self.token = token;
+            self
+        }
+
+        // Timeout for a whole request, including reading the body of downloads
+        pub fn timeout(mut self, timeout: Duration) -> Self {
+            self.timeout = Some(timeout);
+            self
+        }
+
+        pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+            self.connect_timeout = Some(timeout);
+            self
+        }
+
+        // Number of times a request is retried after a connection error or 5xx response.
+        // The delay doubles after every attempt.
+        pub fn retries(mut self, retries: u32, delay: Duration) -> Self {
+            self.retries = retries;
+            self.retry_delay = delay;
+            self
+        }
+
+        pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+            self.proxy = Some(proxy.into());
+            self
+        }
+
+        // PEM encoded certificate to trust in addition to the system roots
+        pub fn ca_certificate(mut self, path: impl Into<PathBuf>) -> Self {
+            self.ca_certificate = Some(path.into());
+            self
+        }
+
+        pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+            self.headers.push((name.into(), value.into()));
+            self
+        }
+
+        pub fn build(self) -> Result<Client> {
+            let mut headers = reqwest::header::HeaderMap::new();
+            for (name, value) in &self.headers {
+                let name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|_| Error::Config(format!("Invalid header name: {:?}", name)))?;
+                let value = reqwest::header::HeaderValue::from_str(value)
+                    .map_err(|_| Error::Config(format!("Invalid header value: {:?}", value)))?;
+                headers.insert(name, value);
+            }
+            let mut builder = reqwest::Client::builder()
+                .user_agent("protonctl-rs")
+                .default_headers(headers);
+            if let Some(timeout) = self.timeout {
+                builder = builder.timeout(timeout);
+            }
+            if let Some(timeout) = self.connect_timeout {
+                builder = builder.connect_timeout(timeout);
+            }
+            if let Some(proxy) = &self.proxy {
+                builder = builder.proxy(
+                    reqwest::Proxy::all(proxy)
+                        .map_err(|_| Error::Config(format!("Invalid proxy: {:?}", proxy)))?,
+                );
+            }
+            if let Some(path) = &self.ca_certificate {
+                let pem = std::fs::read(path).map_err(|e| {
+                    Error::io(format!("Failed to read CA certificate: {:?}", path), e)
+                })?;
+                let certificate = reqwest::Certificate::from_pem(&pem)
+                    .map_err(|_| Error::Config(format!("Invalid CA certificate: {:?}", path)))?;
+                builder = builder.add_root_certificate(certificate);
+            }
+            Ok(Client {
+                http: builder.build()?,
+                base_url: self.base_url,
+                token: self.token,
+                retries: self.retries,
+                retry_delay: self.retry_delay,
+            })
+        }
+    }
+
+    // Connection to the GitHub releases api. Repositories are given as owner/name.
+    #[derive(Debug, Clone)]
+    pub struct Client {
+        http: reqwest::Client,
+        base_url: String,
+        token: Option<String>,
+        retries: u32,
+        retry_delay: Duration,
+    }
+
+    impl Client {
+        pub fn new() -> Result<Self> {
+            Self::builder().build()
+        }
+
+        pub fn builder() -> ClientBuilder {
+            ClientBuilder::default()
+        }
+
+        pub fn base_url(&self) -> &str {
+            &self.base_url
+        }
+
+        pub fn releases_url(&self, repo: &str) -> String {
+            format!("{}/repos/{}/releases", self.base_url, repo)
+        }
+
+        pub fn asset_url(&self, repo: &str, asset: &AssetId) -> String {
+            format!("{}/assets/{}", self.releases_url(repo), asset.id)
+        }
+
+        fn get(&self, url: &str) -> reqwest::RequestBuilder {
+            let request = self.http.get(url);
+            match &self.token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            }
+        }
+
+        // Send the request, retrying connection errors and server errors
+        async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+            let mut delay = self.retry_delay;
+            for _ in 0..self.retries {
+                let Some(attempt) = request.try_clone() else {
+                    break;
+                };
+                match attempt.send().await {
+                    Ok(response) if !response.status().is_server_error() => return Ok(response),
+                    Err(e) if !(e.is_connect() || e.is_timeout()) => return Err(e.into()),
+                    _ => {}
+                }
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            Ok(request.send().await?)
+        }
+
+        pub async fn releases(
+            &self,
+            repo: &str,
+            per_page: Option<u8>,
+            page: Option<u8>,
+        ) -> Result<Releases> {
+            let pp: u8 = per_page.unwrap_or(10);
+            let p: u8 = page.unwrap_or(1);
+
+            let url = self.releases_url(repo);
+            let response = self
+                .send(self.get(&url).query(&[("per_page", pp), ("page", p)]))
+                .await?;
+            Ok(self
+                .check_response(response, &url)
+                .await?
+                .json::<Releases>()
+                .await?)
+        }
+
+        pub async fn latest_release(&self, repo: &str) -> Result<Release> {
+            let url = format!("{}/latest", self.releases_url(repo));
+            let response = self.send(self.get(&url)).await?;
+            Ok(self
+                .check_response(response, "latest release")
+                .await?
+                .json::<Release>()
+                .await?)
+        }
+
+        pub async fn release_version(&self, repo: &str, version: &str) -> Result<Release> {
+            let url = format!("{}/tags/{}", self.releases_url(repo), version);
+            let response = self.send(self.get(&url)).await?;
+            let what = format!("release {}", version);
+            Ok(self
+                .check_response(response, &what)
+                .await?
+                .json::<Release>()
+                .await?)
+        }
+
+        pub async fn download_asset(
+            &self,
+            repo: &str,
+            asset: &AssetId,
+        ) -> Result<reqwest::Response> {
+            self.download_asset_from(repo, asset, 0).await
+        }
+
+        // Request the asset starting at offset bytes. The server may ignore the range and send
+        // the whole asset, so callers should check for 206 Partial Content.
+        pub async fn download_asset_from(
+            &self,
+            repo: &str,
+            asset: &AssetId,
+            offset: u64,
+        ) -> Result<reqwest::Response> {
+            let mut request = self
+                .get(&self.asset_url(repo, asset))
+                .header("Accept", "application/octet-stream");
+            if offset > 0 {
+                request = request.header("Range", format!("bytes={}-", offset));
+            }
+            self.send(request).await
+        }
+
+        pub async fn download_asset_to_memory(
+            &self,
+            repo: &str,
+            asset: &AssetId,
+        ) -> Result<String> {
+            let request = self
+                .get(&self.asset_url(repo, asset))
+                .header("Accept", "application/octet-stream");
+            let response = self.send(request).await?;
+            Ok(self
+                .check_response(response, &asset.name)
+                .await?
+                .text()
+                .await?)
+        }
+
+        // Turn error responses into an Error instead of letting them fail to deserialize.
+        // what describes the requested resource for NotFound errors.
+        async fn check_response(
+            &self,
+            response: reqwest::Response,
+            what: &str,
+        ) -> Result<reqwest::Response> {
+            let status = response.status();
+            if status.is_success() {
+                return Ok(response);
+            }
+            if status == reqwest::StatusCode::NOT_FOUND {
+                return Err(Error::NotFound(what.to_string()));
+            }
+            if matches!(
+                status,
+                reqwest::StatusCode::FORBIDDEN | reqwest::StatusCode::TOO_MANY_REQUESTS
+            ) {
+                // Primary limits empty x-ratelimit-remaining, secondary limits send retry-after
+                let rate_limit = RateLimit::from_headers(response.headers());
+                let retry_after = response
+                    .headers()
+                    .get("retry-after")
+                    .and_then(|v| v.to_str().ok()?.parse::<u64>().ok());
+                if let Some(retry_after) = retry_after {
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or_default();
+                    return Err(Error::RateLimited {
+                        reset: Some(now + retry_after),
+                        authenticated: self.token.is_some(),
+                    });
+                }
+                if let Some(rate_limit) = rate_limit.filter(|r| r.remaining == 0) {
+                    return Err(Error::RateLimited {
+                        reset: Some(rate_limit.reset),
+                        authenticated: self.token.is_some(),
+                    });
+                }
+            }
+            let message = response
+                .json::<ApiMessage>()
+                .await
+                .map(|m| m.message)
+                .unwrap_or_default();
+            Err(Error::Api {
+                status: status.as_u16(),
+                message,
+            })
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct ApiMessage {
+        message: String,
     }
 
     pub fn get_asset_id(release: &Release, asset_type: AssetType) -> AssetId {
@@ -117,83 +441,158 @@ pub mod api {
         }
         found_asset
     }
-
-    pub async fn download_asset(
-        mut url: String,
-        asset: &AssetId,
-    ) -> Result<reqwest::Response, reqwest::Error> {
-        url.push_str(format!("/assets/{}", asset.id).as_str());
-        reqwest::Client::new()
-            .get(url)
-            .header("user-agent", "protonctl-rs")
-            .header("Accept", "application/octet-stream")
-            .send()
-            .await
-    }
-
-    pub async fn download_asset_to_memory(
-        mut url: String,
-        asset: &AssetId,
-    ) -> Result<String, reqwest::Error> {
-        url.push_str(format!("/assets/{}", asset.id).as_str());
-        reqwest::Client::new()
-            .get(url)
-            .header("user-agent", "protonctl-rs")
-            .header("Accept", "application/octet-stream")
-            .send()
-            .await?
-            .text()
-            .await
-    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::github::api::{Client, Release};
+    use crate::install_type::InstallType;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const RELEASES: &str = "/repos/GloriousEggroll/proton-ge-custom/releases";
+
+    fn release(tag: &str) -> serde_json::Value {
+        serde_json::json!({
+            "html_url": format!("https://github.com/GloriousEggroll/proton-ge-custom/releases/tag/{}", tag),
+            "tag_name": tag,
+            "body": "",
+            "assets": [
+                { "name": format!("{}.sha512sum", tag), "id": 1, "size": 160 },
+                { "name": format!("{}.tar.gz", tag), "id": 2, "size": 1024 },
+            ],
+        })
+    }
+
+    async fn client(server: &MockServer) -> crate::error::Result<Client> {
+        Client::builder().base_url(server.uri()).token(None).build()
+    }
+
     #[tokio::test]
-    async fn can_get_releases() -> Result<(), reqwest::Error> {
-        use crate::github::api::releases;
-        use crate::install_type::InstallType;
+    async fn can_get_releases() -> crate::error::Result<()> {
+        let server = MockServer::start().await;
+        let releases: Vec<serde_json::Value> = (1..=50)
+            .map(|minor| release(&format!("GE-Proton8-{}", minor)))
+            .collect();
+        Mock::given(method("GET"))
+            .and(path(RELEASES))
+            .and(query_param("per_page", "50"))
+            .and(query_param("page", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(releases))
+            .mount(&server)
+            .await;
+
         let install = InstallType::Proton;
-        let result = releases(&install.get_url(false), Some(50), Some(1)).await?;
+        let result = client(&server)
+            .await?
+            .releases(&install.repository(), Some(50), Some(1))
+            .await?;
         assert_eq!(result.len(), 50);
         Ok(())
     }
 
     #[tokio::test]
-    async fn can_get_latest_release() -> Result<(), reqwest::Error> {
-        use crate::github::api::latest_release;
-        use crate::install_type::InstallType;
+    async fn can_get_latest_release() -> crate::error::Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(format!("{}/latest", RELEASES)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(release("GE-Proton8-25")))
+            .mount(&server)
+            .await;
 
         let install = InstallType::Proton;
-        let _result = latest_release(&install.get_url(true)).await?;
+        let result = client(&server)
+            .await?
+            .latest_release(&install.repository())
+            .await?;
+        assert_eq!(result.tag_name, "GE-Proton8-25");
         Ok(())
     }
 
     #[tokio::test]
-    async fn can_get_release_by_tag() -> Result<(), reqwest::Error> {
-        use crate::github::api::{release_version, Release};
-        use crate::install_type::InstallType;
+    async fn can_get_release_by_tag() -> crate::error::Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(format!("{}/tags/GE-Proton8-4", RELEASES)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(release("GE-Proton8-4")))
+            .mount(&server)
+            .await;
         let version: String = String::from("GE-Proton8-4");
 
         let install = InstallType::Proton;
-        let release: Release =
-            release_version(&install.get_url(false), &String::from("GE-Proton8-4")).await?;
+        let release: Release = client(&server)
+            .await?
+            .release_version(&install.repository(), &String::from("GE-Proton8-4"))
+            .await?;
         assert_eq!(release.tag_name, version);
         Ok(())
     }
 
     #[tokio::test]
-    async fn can_get_asset_ids() -> Result<(), reqwest::Error> {
-        use crate::github::api::{get_asset_id, release_version, AssetType, Release};
-        use crate::install_type::InstallType;
+    async fn can_get_asset_ids() -> crate::error::Result<()> {
+        use crate::github::api::{get_asset_id, AssetType};
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(format!("{}/tags/GE-Proton8-4", RELEASES)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(release("GE-Proton8-4")))
+            .mount(&server)
+            .await;
         let install = InstallType::Proton;
 
-        let release: Release =
-            release_version(&install.get_url(false), &String::from("GE-Proton8-4")).await?;
+        let release: Release = client(&server)
+            .await?
+            .release_version(&install.repository(), &String::from("GE-Proton8-4"))
+            .await?;
         let sha_asset = get_asset_id(&release, AssetType::Sha);
         let tar_asset = get_asset_id(&release, AssetType::Tar);
         assert_eq!(tar_asset.name, String::from("GE-Proton8-4.tar.gz"));
         assert_eq!(sha_asset.name, String::from("GE-Proton8-4.sha512sum"));
         Ok(())
     }
+
+    #[tokio::test]
+    async fn missing_release_is_not_found() -> crate::error::Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(404)
+                    .set_body_json(serde_json::json!({"message": "Not Found"})),
+            )
+            .mount(&server)
+            .await;
+
+        let result = client(&server)
+            .await?
+            .release_version(&InstallType::Proton.repository(), "GE-Proton0-0")
+            .await;
+        assert!(matches!(result, Err(crate::error::Error::NotFound(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn exhausted_rate_limit_is_reported() -> crate::error::Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(403)
+                    .insert_header("x-ratelimit-limit", "60")
+                    .insert_header("x-ratelimit-remaining", "0")
+                    .insert_header("x-ratelimit-reset", "1700000000"),
+            )
+            .mount(&server)
+            .await;
+
+        let result = client(&server)
+            .await?
+            .latest_release(&InstallType::Proton.repository())
+            .await;
+        assert!(matches!(
+            result,
+            Err(crate::error::Error::RateLimited {
+                reset: Some(1700000000),
+                authenticated: false
+            })
+        ));
+        Ok(())
+    }
 }
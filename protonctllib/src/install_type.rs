@@ -1,8 +1,15 @@
 use crate::constants;
+use crate::error::{Error, Result};
+use crate::lutris;
+use crate::steam;
+use crate::target::Target;
 use core::fmt::Display;
 use dirs::home_dir;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
-#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Debug)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum InstallType {
     Proton,
     Wine,
@@ -20,73 +27,86 @@ impl Display for InstallType {
 }
 
 impl InstallType {
-    pub fn get_url(&self, latest: bool) -> String {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "proton" => Some(InstallType::Proton),
+            "wine" => Some(InstallType::Wine),
+            "ulwgl" => Some(InstallType::ULWGL),
+            _ => None,
+        }
+    }
+
+    // The GitHub repository releases are published to, as owner/name
+    pub fn repository(&self) -> String {
         match self {
-            InstallType::Wine => {
-                if latest {
-                    format!(
-                        "https://api.github.com/repos/{}/{}/releases/latest",
-                        constants::GE_PROJECT_OWNER,
-                        constants::WINE_PROJECT_NAME
-                    )
-                } else {
-                    format!(
-                        "https://api.github.com/repos/{}/{}/releases",
-                        constants::GE_PROJECT_OWNER,
-                        constants::WINE_PROJECT_NAME
-                    )
-                }
-            }
-            InstallType::ULWGL => {
-                if latest {
-                    format!(
-                        "https://api.github.com/repos/{}/{}/releases/latest",
-                        constants::ULWGL_PROJECT_OWNER,
-                        constants::ULWGL_PROJECT_NAME
-                    )
-                } else {
-                    format!(
-                        "https://api.github.com/repos/{}/{}/releases",
-                        constants::ULWGL_PROJECT_OWNER,
-                        constants::ULWGL_PROJECT_NAME
-                    )
-                }
-            }
-            InstallType::Proton => {
-                if latest {
-                    format!(
-                        "https://api.github.com/repos/{}/{}/releases/latest",
-                        constants::GE_PROJECT_OWNER,
-                        constants::PROTON_PROJECT_NAME
-                    )
-                } else {
-                    format!(
-                        "https://api.github.com/repos/{}/{}/releases",
-                        constants::GE_PROJECT_OWNER,
-                        constants::PROTON_PROJECT_NAME
-                    )
-                }
-            }
+            InstallType::Wine => format!(
+                "{}/{}",
+                constants::GE_PROJECT_OWNER,
+                constants::WINE_PROJECT_NAME
+            ),
+            InstallType::ULWGL => format!(
+                "{}/{}",
+                constants::ULWGL_PROJECT_OWNER,
+                constants::ULWGL_PROJECT_NAME
+            ),
+            InstallType::Proton => format!(
+                "{}/{}",
+                constants::GE_PROJECT_OWNER,
+                constants::PROTON_PROJECT_NAME
+            ),
         }
     }
 
-    pub fn get_compat_directory_safe(&self) -> anyhow::Result<std::path::PathBuf> {
-        let mut compat_dir =
-            home_dir().ok_or(anyhow::anyhow!("Failed to get users home directory"))?;
+    // Files every release of this type contains, relative to its top level directory
+    pub fn required_files(&self) -> &[&str] {
+        match self {
+            InstallType::Proton => &["proton", "compatibilitytool.vdf"],
+            InstallType::Wine => &["bin/wine"],
+            InstallType::ULWGL => &["proton"],
+        }
+    }
 
-        let compat_path = match self {
-            InstallType::Wine => std::path::PathBuf::from(".local/share/lutris/runners/wine"),
-            InstallType::Proton => {
-                std::path::PathBuf::from(".local/share/Steam/compatibilitytools.d")
-            }
-            InstallType::ULWGL => std::path::PathBuf::from(".local/share/ULWGL-Proton"),
+    // Where Steam, Lutris, Heroic, Bottles and ULWGL look for builds of this type. Native installs
+    // follow XDG_DATA_HOME, XDG_CONFIG_HOME for Heroic, and the detected Steam root, flatpaks
+    // keep their data under ~/.var/app and the snap under ~/snap.
+    pub fn default_compat_directory(&self, target: Target) -> Result<PathBuf> {
+        let data_dir = dirs::data_dir().ok_or(Error::HomeDirMissing)?;
+        let heroic_tools = || -> Result<PathBuf> {
+            Ok(match target {
+                Target::HeroicFlatpak => home_dir()
+                    .ok_or(Error::HomeDirMissing)?
+                    .join(".var/app")
+                    .join(constants::HEROIC_FLATPAK_ID)
+                    .join("config/heroic/tools"),
+                _ => dirs::config_dir()
+                    .ok_or(Error::HomeDirMissing)?
+                    .join("heroic/tools"),
+            })
         };
-        compat_dir.push(compat_path);
-        if !compat_dir.exists() {
-            std::fs::create_dir_all(&compat_dir)?;
-            Ok(compat_dir)
-        } else {
-            Ok(compat_dir)
-        }
+        Ok(match (self, target) {
+            (InstallType::Proton, Target::Heroic | Target::HeroicFlatpak) => {
+                heroic_tools()?.join("proton")
+            }
+            (InstallType::Wine, Target::Heroic | Target::HeroicFlatpak) => {
+                heroic_tools()?.join("wine")
+            }
+            (InstallType::Wine, Target::Bottles) => data_dir.join("bottles/runners"),
+            (InstallType::Wine, Target::BottlesFlatpak) => home_dir()
+                .ok_or(Error::HomeDirMissing)?
+                .join(".var/app")
+                .join(constants::BOTTLES_FLATPAK_ID)
+                .join("data/bottles/runners"),
+            (InstallType::Wine, Target::Native | Target::Flatpak) => {
+                lutris::root_for(target)?.join("runners/wine")
+            }
+            (InstallType::Proton, target) => steam::root_for(target)?.join("compatibilitytools.d"),
+            (InstallType::ULWGL, Target::Native) => data_dir.join("ULWGL-Proton"),
+            _ => {
+                return Err(Error::Config(format!(
+                    "{} can't be installed to {}",
+                    self, target
+                )))
+            }
+        })
     }
 }
@@ -1,6 +1,19 @@
+pub mod config;
 pub mod constants;
 pub mod decompress;
+pub mod error;
 pub mod github;
 pub mod install_type;
+pub mod lutris;
+pub mod manifest;
+pub mod source;
+pub mod steam;
+pub mod target;
+#[cfg(test)]
+mod test_utils;
 pub mod utils;
+pub mod vdf;
+pub mod version;
 pub mod version_info;
+
+pub use error::{Error, Result};
@@ -0,0 +1,240 @@
+// Reading which wine build Lutris games use and changing it. Games are listed in pga.db, which
+// Lutris may have open so it's only ever read, and each game's settings are in a YAML file
+// under games/. Runner wide defaults are in runners/wine.yml.
+use crate::constants;
+use crate::error::{Error, Result};
+use crate::target::Target;
+use crate::utils;
+use rusqlite::{Connection, OpenFlags};
+use serde_yaml::{Mapping, Value};
+use std::path::{Path, PathBuf};
+
+// The targets that are installs of Lutris
+pub const TARGETS: [Target; 2] = [Target::Native, Target::Flatpak];
+
+// The Lutris data directory of a target
+pub fn root_for(target: Target) -> Result<PathBuf> {
+    match target {
+        Target::Native => Ok(dirs::data_dir()
+            .ok_or(Error::HomeDirMissing)?
+            .join("lutris")),
+        Target::Flatpak => Ok(dirs::home_dir()
+            .ok_or(Error::HomeDirMissing)?
+            .join(".var/app")
+            .join(constants::LUTRIS_FLATPAK_ID)
+            .join("data/lutris")),
+        _ => Err(Error::Config(format!("Lutris has no {} install", target))),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Game {
+    pub id: i64,
+    pub name: String,
+    pub slug: String,
+    // The game's YAML config
+    pub config: PathBuf,
+}
+
+// The games that run with wine, sorted by name. Empty if Lutris hasn't been run.
+pub fn wine_games(root: &Path) -> Result<Vec<Game>> {
+    let path = root.join("pga.db");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let database_error = |source| Error::Database {
+        path: path.clone(),
+        source,
+    };
+    let connection = Connection::open_with_flags(
+        &path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(database_error)?;
+    let mut statement = connection
+        .prepare(
+            "SELECT id, name, slug, configpath FROM games \
+             WHERE runner = 'wine' AND configpath IS NOT NULL AND configpath != '' \
+             ORDER BY name COLLATE NOCASE",
+        )
+        .map_err(database_error)?;
+    let games = statement
+        .query_map([], |row| {
+            Ok(Game {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                slug: row.get(2)?,
+                config: root
+                    .join("games")
+                    .join(format!("{}.yml", row.get::<_, String>(3)?)),
+            })
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(database_error)?;
+    Ok(games)
+}
+
+// Look a game up by id, slug, name or a part of the name that only one game has
+pub fn find_game<'a>(games: &'a [Game], query: &str) -> Result<&'a Game> {
+    if let Some(game) = games.iter().find(|g| {
+        g.id.to_string() == query || g.slug == query || g.name.eq_ignore_ascii_case(query)
+    }) {
+        return Ok(game);
+    }
+    let query_lower = query.to_lowercase();
+    let matches: Vec<_> = games
+        .iter()
+        .filter(|g| g.name.to_lowercase().contains(&query_lower))
+        .collect();
+    match matches.as_slice() {
+        [game] => Ok(game),
+        [] => Err(Error::NotFound(format!("Lutris game {:?}", query))),
+        _ => Err(Error::Config(format!(
+            "{:?} matches more than one game: {}. Use the slug instead",
+            query,
+            matches
+                .iter()
+                .map(|g| format!("{} ({})", g.name, g.slug))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+// A game or runner config. Both keep the wine build under wine.version.
+#[derive(Debug, Clone)]
+pub struct LutrisConfig {
+    pub path: PathBuf,
+    yaml: Mapping,
+}
+
+impl LutrisConfig {
+    // A file that doesn't exist yet loads as empty
+    pub fn load(path: &Path) -> Result<Self> {
+        let yaml = match std::fs::read_to_string(path) {
+            Ok(contents) if contents.trim().is_empty() => Mapping::new(),
+            Ok(contents) => serde_yaml::from_str(&contents).map_err(|source| Error::Yaml {
+                path: path.to_path_buf(),
+                source,
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Mapping::new(),
+            Err(e) => return Err(Error::io(format!("Failed to read {:?}", path), e)),
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            yaml,
+        })
+    }
+
+    // The runner wide defaults
+    pub fn runner(root: &Path) -> Result<Self> {
+        Self::load(&root.join("runners/wine.yml"))
+    }
+
+    pub fn wine_version(&self) -> Option<&str> {
+        self.yaml.get("wine")?.get("version")?.as_str()
+    }
+
+    pub fn set_wine_version(&mut self, version: &str) {
+        let wine = self
+            .yaml
+            .entry(Value::from("wine"))
+            .or_insert_with(|| Value::Mapping(Mapping::new()));
+        if !wine.is_mapping() {
+            *wine = Value::Mapping(Mapping::new());
+        }
+        if let Value::Mapping(wine) = wine {
+            wine.insert(Value::from("version"), Value::from(version));
+        }
+    }
+
+    // Returns whether a version was set
+    pub fn unset_wine_version(&mut self) -> bool {
+        match self.yaml.get_mut("wine").and_then(|w| w.as_mapping_mut()) {
+            Some(wine) => wine.remove("version").is_some(),
+            None => false,
+        }
+    }
+
+    // Back up the file on disk, if there is one, and replace it. Returns the path of the backup.
+    pub fn save(&self) -> Result<Option<PathBuf>> {
+        let backup = if self.path.exists() {
+            Some(utils::backup(&self.path)?)
+        } else {
+            if let Some(parent) = self.path.parent() {
+                utils::create_directory_safe(parent)?;
+            }
+            None
+        };
+        let contents = serde_yaml::to_string(&self.yaml).map_err(|source| Error::Yaml {
+            path: self.path.clone(),
+            source,
+        })?;
+        utils::write_atomic(&self.path, &contents)?;
+        Ok(backup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lutris::{find_game, wine_games, LutrisConfig};
+    use crate::test_utils::TempDir;
+    use rusqlite::Connection;
+
+    #[test]
+    fn can_find_wine_games() -> std::io::Result<()> {
+        let root = TempDir::new("lutris-games");
+        assert!(wine_games(&root).unwrap().is_empty());
+        let connection = Connection::open(root.join("pga.db")).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE games (id INTEGER PRIMARY KEY, name TEXT, slug TEXT, runner TEXT, configpath TEXT);
+                 INSERT INTO games VALUES (1, 'World of Warcraft', 'world-of-warcraft', 'wine', 'world-of-warcraft-1');
+                 INSERT INTO games VALUES (2, 'Celeste', 'celeste', 'linux', 'celeste-2');
+                 INSERT INTO games VALUES (3, 'Diablo IV', 'diablo-iv', 'wine', 'diablo-iv-3');
+                 INSERT INTO games VALUES (4, 'Warcraft III', 'warcraft-iii', 'wine', '');",
+            )
+            .unwrap();
+        drop(connection);
+
+        let games = wine_games(&root).unwrap();
+        let names: Vec<_> = games.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, vec!["Diablo IV", "World of Warcraft"]);
+        assert_eq!(games[0].config, root.join("games/diablo-iv-3.yml"));
+        assert_eq!(find_game(&games, "diablo").unwrap().id, 3);
+        assert_eq!(find_game(&games, "1").unwrap().slug, "world-of-warcraft");
+        assert!(find_game(&games, "Celeste").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn can_edit_wine_versions() -> std::io::Result<()> {
+        let root = TempDir::new("lutris-configs");
+        let mut runner = LutrisConfig::runner(&root).unwrap();
+        assert_eq!(runner.wine_version(), None);
+        runner.set_wine_version("lutris-GE-Proton8-26-x86_64");
+        assert!(runner.save().unwrap().is_none());
+
+        std::fs::create_dir_all(root.join("games"))?;
+        let path = root.join("games/diablo-iv-3.yml");
+        std::fs::write(
+            &path,
+            "game:\n  exe: drive_c/Diablo IV Launcher.exe\n  prefix: /games/diablo-iv\nsystem: {}\nwine:\n  version: lutris-7.2-2-x86_64\n",
+        )?;
+        let mut game = LutrisConfig::load(&path).unwrap();
+        assert_eq!(game.wine_version(), Some("lutris-7.2-2-x86_64"));
+        game.set_wine_version("lutris-GE-Proton8-26-x86_64");
+        let backup = game.save().unwrap().unwrap();
+        assert!(std::fs::read_to_string(backup)?.contains("lutris-7.2-2-x86_64"));
+
+        let game = LutrisConfig::load(&path).unwrap();
+        assert_eq!(game.wine_version(), Some("lutris-GE-Proton8-26-x86_64"));
+        let contents = std::fs::read_to_string(&path)?;
+        assert!(contents.starts_with("game:\n  exe: drive_c/Diablo IV Launcher.exe\n"));
+        let mut runner = LutrisConfig::runner(&root).unwrap();
+        assert_eq!(runner.wine_version(), Some("lutris-GE-Proton8-26-x86_64"));
+        assert!(runner.unset_wine_version());
+        assert!(!runner.unset_wine_version());
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+// Record of the builds protonctl has installed, kept in the download directory
+use crate::constants;
+use crate::error::{Error, Result};
+use crate::target::Target;
+use crate::utils;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub tag: String,
+    // proton, wine, ulwgl or the name of a source from the config. Older manifests called
+    // this install_type
+    #[serde(alias = "install_type")]
+    pub source: String,
+    pub asset_name: String,
+    pub asset_id: u64,
+    pub sha512: String,
+    // Seconds since the unix epoch
+    pub installed_at: u64,
+    // Older manifests only had a flatpak flag
+    #[serde(alias = "flatpak", deserialize_with = "target_or_flatpak")]
+    pub target: Target,
+    // Top level directory extracted into the compatibility directory
+    pub directory: String,
+}
+
+fn target_or_flatpak<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Target, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum TargetOrFlatpak {
+        Target(Target),
+        Flatpak(bool),
+    }
+    Ok(match TargetOrFlatpak::deserialize(deserializer)? {
+        TargetOrFlatpak::Target(target) => target,
+        TargetOrFlatpak::Flatpak(true) => Target::Flatpak,
+        TargetOrFlatpak::Flatpak(false) => Target::Native,
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Manifest {
+    pub installs: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn path(download_directory: &Path) -> PathBuf {
+        download_directory.join(constants::MANIFEST_FILE)
+    }
+
+    // Load the manifest, treating a missing file as an empty manifest
+    pub fn load(download_directory: &Path) -> Result<Self> {
+        let path = Self::path(download_directory);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| Error::io(format!("Failed to read manifest: {:?}", path), e))?;
+        serde_json::from_str(&contents).map_err(|source| Error::Manifest { path, source })
+    }
+
+    // Write to a temporary file first so an interrupted save can't corrupt the manifest
+    pub fn save(&self, download_directory: &Path) -> Result<()> {
+        let path = Self::path(download_directory);
+        let contents = serde_json::to_string_pretty(self).map_err(|source| Error::Manifest {
+            path: path.clone(),
+            source,
+        })?;
+        utils::write_atomic(&path, &contents)
+    }
+
+    pub fn find(&self, source: &str, target: Target, directory: &str) -> Option<&ManifestEntry> {
+        self.installs
+            .iter()
+            .find(|e| e.source == source && e.target == target && e.directory == directory)
+    }
+
+    // Find the install of a release by its tag, for sources whose directory names can't be
+    // worked out from the tag
+    pub fn find_tag(&self, source: &str, target: Target, tag: &str) -> Option<&ManifestEntry> {
+        self.installs
+            .iter()
+            .find(|e| e.source == source && e.target == target && e.tag == tag)
+    }
+
+    pub fn is_managed(&self, source: &str, target: Target, directory: &str) -> bool {
+        self.find(source, target, directory).is_some()
+    }
+
+    // Add an entry, replacing any previous install of the same directory
+    pub fn add(&mut self, entry: ManifestEntry) {
+        self.remove(&entry.source, entry.target, &entry.directory);
+        self.installs.push(entry);
+    }
+
+    pub fn remove(
+        &mut self,
+        source: &str,
+        target: Target,
+        directory: &str,
+    ) -> Option<ManifestEntry> {
+        let index = self
+            .installs
+            .iter()
+            .position(|e| e.source == source && e.target == target && e.directory == directory)?;
+        Some(self.installs.remove(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::manifest::{Manifest, ManifestEntry};
+    use crate::target::Target;
+
+    fn entry(directory: &str, target: Target) -> ManifestEntry {
+        ManifestEntry {
+            tag: directory.to_string(),
+            source: String::from("proton"),
+            asset_name: format!("{}.tar.gz", directory),
+            asset_id: 1,
+            sha512: String::new(),
+            installed_at: 0,
+            target,
+            directory: directory.to_string(),
+        }
+    }
+
+    #[test]
+    fn can_add_and_remove_entries() {
+        let mut manifest = Manifest::default();
+        manifest.add(entry("GE-Proton8-25", Target::Native));
+        manifest.add(entry("GE-Proton8-25", Target::Native));
+        manifest.add(entry("GE-Proton8-25", Target::Flatpak));
+        manifest.add(entry("GE-Proton8-25", Target::Snap));
+        assert_eq!(manifest.installs.len(), 3);
+        assert!(manifest.is_managed("proton", Target::Native, "GE-Proton8-25"));
+        assert!(!manifest.is_managed("wine", Target::Native, "GE-Proton8-25"));
+
+        assert!(manifest
+            .remove("proton", Target::Flatpak, "GE-Proton8-25")
+            .is_some());
+        assert!(!manifest.is_managed("proton", Target::Flatpak, "GE-Proton8-25"));
+        assert!(manifest.is_managed("proton", Target::Native, "GE-Proton8-25"));
+    }
+
+    #[test]
+    fn can_read_install_type_manifests() {
+        let manifest: Manifest = serde_json::from_str(
+            r#"{"installs": [{"tag": "GE-Proton8-25", "install_type": "proton",
+                "asset_name": "GE-Proton8-25.tar.gz", "asset_id": 1, "sha512": "",
+                "installed_at": 0, "flatpak": true, "directory": "GE-Proton8-25"}]}"#,
+        )
+        .unwrap();
+        assert!(manifest.is_managed("proton", Target::Flatpak, "GE-Proton8-25"));
+    }
+}
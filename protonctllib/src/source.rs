@@ -0,0 +1,254 @@
+// Where releases come from and where they're installed: one of the built in install types or a
+// source declared in the user config
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::github::api::{get_asset_id, AssetId, AssetType, Release};
+use crate::install_type::InstallType;
+use crate::target::Target;
+use crate::utils;
+use crate::version::{self, Version};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::path::PathBuf;
+
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug)]
+pub enum Source {
+    Builtin(InstallType),
+    Custom(CustomSource),
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Source::Builtin(InstallType::Proton)
+    }
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Builtin(install_type) => write!(f, "{}", install_type),
+            Source::Custom(source) => write!(f, "{}", source.name),
+        }
+    }
+}
+
+impl From<InstallType> for Source {
+    fn from(install_type: InstallType) -> Self {
+        Source::Builtin(install_type)
+    }
+}
+
+impl Source {
+    // Look a name up in the built in types first and then the sources in the config
+    pub fn resolve(name: &str, config: &Config) -> Result<Self> {
+        if let Some(install_type) = InstallType::from_name(name) {
+            return Ok(install_type.into());
+        }
+        match config.source(name) {
+            Some(source) => Ok(Source::Custom(source.clone())),
+            None => Err(Error::Config(format!(
+                "Unknown install type {:?}. Expected proton, wine, ulwgl or a source from the config file",
+                name
+            ))),
+        }
+    }
+
+    // None for custom sources, which don't follow the GE naming scheme
+    pub fn install_type(&self) -> Option<InstallType> {
+        match self {
+            Source::Builtin(install_type) => Some(*install_type),
+            Source::Custom(_) => None,
+        }
+    }
+
+    pub fn repository(&self) -> String {
+        match self {
+            Source::Builtin(install_type) => install_type.repository(),
+            Source::Custom(source) => source.repository(),
+        }
+    }
+
+    pub fn tarball_asset(&self, release: &Release) -> Option<AssetId> {
+        match self {
+            Source::Builtin(_) => {
+                Some(get_asset_id(release, AssetType::Tar)).filter(|a| !a.is_empty())
+            }
+            Source::Custom(source) => source.tarball_asset(release),
+        }
+    }
+
+    pub fn checksum_asset(&self, release: &Release) -> Option<AssetId> {
+        match self {
+            Source::Builtin(_) => {
+                Some(get_asset_id(release, AssetType::Sha)).filter(|a| !a.is_empty())
+            }
+            Source::Custom(source) => source.checksum_asset(release),
+        }
+    }
+
+    // The directory a release is extracted to, if it can be worked out from the tag. Bottles
+    // only picks up wine builds under its own names.
+    pub fn directory_name(&self, tag: &str, target: Target) -> Option<String> {
+        Some(match (self.install_type()?, target) {
+            (InstallType::Wine, Target::Bottles | Target::BottlesFlatpak) => {
+                version::bottles_directory_name(tag)
+            }
+            (install_type, _) => version::directory_name(install_type, tag),
+        })
+    }
+
+    pub fn version_of_directory(&self, name: &str) -> Option<Version> {
+        Version::from_directory(self.install_type()?, name)
+    }
+
+    // The directory releases are installed into for a target
+    pub fn compat_directory(&self, config: &Config, target: Target) -> Result<PathBuf> {
+        match self {
+            Source::Builtin(install_type) => config.compat_directory(*install_type, target),
+            Source::Custom(source) => source.compat_directory(target),
+        }
+    }
+
+    // The compatibility directory, created if it doesn't exist
+    pub fn get_compat_directory_safe(&self, config: &Config, target: Target) -> Result<PathBuf> {
+        utils::create_directory_safe(&self.compat_directory(config, target)?)
+    }
+}
+
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomSource {
+    // Selected with -t <name>
+    pub name: String,
+    pub owner: String,
+    pub repo: String,
+    // Glob matched against the release asset names, e.g. wine-*-amd64.tar.xz
+    pub asset_pattern: String,
+    // Glob for the sha512sum asset. Releases are installed unverified without one
+    pub checksum_pattern: Option<String>,
+    // Install directories, relative to the home directory unless absolute
+    pub directory: PathBuf,
+    pub flatpak_directory: Option<PathBuf>,
+}
+
+impl CustomSource {
+    // The GitHub repository releases are published to, as owner/name
+    pub fn repository(&self) -> String {
+        format!("{}/{}", self.owner, self.repo)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let invalid =
+            |reason: &str| Err(Error::Config(format!("source {:?} {}", self.name, reason)));
+        if self.name.is_empty() {
+            return Err(Error::Config(String::from("source names can't be empty")));
+        }
+        if self.owner.is_empty() || self.repo.is_empty() {
+            return invalid("needs both an owner and a repo");
+        }
+        if self.owner.contains('/') || self.repo.contains('/') {
+            return invalid("owner and repo can't contain '/'");
+        }
+        if self.asset_pattern.is_empty() {
+            return invalid("has an empty asset_pattern");
+        }
+        if self.directory.as_os_str().is_empty() {
+            return invalid("has an empty directory");
+        }
+        Ok(())
+    }
+
+    pub fn compat_directory(&self, target: Target) -> Result<PathBuf> {
+        let directory = match target {
+            Target::Native => &self.directory,
+            Target::Flatpak => self
+                .flatpak_directory
+                .as_ref()
+                .ok_or(Error::Config(format!(
+                    "source {:?} has no flatpak_directory",
+                    self.name
+                )))?,
+            _ => {
+                return Err(Error::Config(format!(
+                    "source {:?} can't be installed to {}",
+                    self.name, target
+                )))
+            }
+        };
+        utils::home_path(directory)
+    }
+
+    pub fn tarball_asset(&self, release: &Release) -> Option<AssetId> {
+        find_asset(release, &self.asset_pattern)
+    }
+
+    pub fn checksum_asset(&self, release: &Release) -> Option<AssetId> {
+        find_asset(release, self.checksum_pattern.as_ref()?)
+    }
+}
+
+fn find_asset(release: &Release, pattern: &str) -> Option<AssetId> {
+    release
+        .assets
+        .iter()
+        .find(|asset| utils::matches_pattern(pattern, &asset.name))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::install_type::InstallType;
+    use crate::source::Source;
+    use crate::target::Target;
+    use std::path::PathBuf;
+
+    #[test]
+    fn can_resolve_sources() {
+        let config = Config::parse(
+            r#"
+[paths]
+wine_flatpak = "/opt/wine"
+
+[[sources]]
+name = "kron4ek"
+owner = "Kron4ek"
+repo = "Wine-Builds"
+asset_pattern = "wine-*-amd64.tar.xz"
+directory = "/opt/kron4ek"
+"#,
+        )
+        .unwrap();
+
+        let wine = Source::resolve("wine", &config).unwrap();
+        assert_eq!(wine, Source::Builtin(InstallType::Wine));
+        assert_eq!(wine.repository(), "GloriousEggroll/wine-ge-custom");
+        assert_eq!(
+            wine.directory_name("GE-Proton8-26", Target::Native)
+                .unwrap(),
+            "lutris-GE-Proton8-26-x86_64"
+        );
+        assert_eq!(
+            wine.directory_name("GE-Proton8-26", Target::Bottles)
+                .unwrap(),
+            "wine-ge-proton8-26"
+        );
+        assert_eq!(
+            wine.compat_directory(&config, Target::Flatpak).unwrap(),
+            PathBuf::from("/opt/wine")
+        );
+
+        let custom = Source::resolve("kron4ek", &config).unwrap();
+        assert_eq!(custom.to_string(), "kron4ek");
+        assert_eq!(custom.repository(), "Kron4ek/Wine-Builds");
+        assert!(custom.install_type().is_none());
+        assert!(custom.directory_name("10.0", Target::Native).is_none());
+        assert_eq!(
+            custom.compat_directory(&config, Target::Native).unwrap(),
+            PathBuf::from("/opt/kron4ek")
+        );
+        assert!(custom.compat_directory(&config, Target::Flatpak).is_err());
+
+        assert!(Source::resolve("tkg", &config).is_err());
+    }
+}
@@ -0,0 +1,531 @@
+// Locating Steam installs and reading and editing their config and library folders
+use crate::constants;
+use crate::error::{Error, Result};
+use crate::target::Target;
+use crate::utils;
+use crate::vdf::{self, Object, Value};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+// Where per game compatibility tools are kept in config/config.vdf. App id 0 is the Steam Play
+// default for every game.
+const COMPAT_TOOL_MAPPING: [&str; 5] = [
+    "InstallConfigStore",
+    "Software",
+    "Valve",
+    "Steam",
+    "CompatToolMapping",
+];
+
+// Find the Steam root. ~/.steam/root and ~/.steam/steam are symlinks Steam maintains to wherever
+// it's installed, so they're followed first. Distro packages install to known places otherwise.
+pub fn find_root(home: &Path, data_dir: &Path) -> Option<PathBuf> {
+    let candidates = [
+        home.join(".steam/root"),
+        home.join(".steam/steam"),
+        data_dir.join("Steam"),
+        home.join(".local/share/Steam"),
+        home.join(".steam/debian-installation"),
+    ];
+    candidates
+        .iter()
+        .filter_map(|path| path.canonicalize().ok())
+        .find(|path| path.join("steamapps").is_dir() || path.join("compatibilitytools.d").is_dir())
+}
+
+// The Steam root for this user, falling back to the XDG data directory when Steam can't be
+// found so a fresh install still has somewhere to go
+pub fn root() -> Option<PathBuf> {
+    let home = dirs::home_dir()?;
+    let data_dir = dirs::data_dir()?;
+    Some(find_root(&home, &data_dir).unwrap_or(data_dir.join("Steam")))
+}
+
+// The targets that are installs of Steam
+pub const TARGETS: [Target; 3] = [Target::Native, Target::Flatpak, Target::Snap];
+
+// The Steam root of a target
+pub fn root_for(target: Target) -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or(Error::HomeDirMissing)?;
+    Ok(match target {
+        Target::Native => root().ok_or(Error::HomeDirMissing)?,
+        Target::Flatpak => home
+            .join(".var/app")
+            .join(constants::STEAM_FLATPAK_ID)
+            .join(".local/share/Steam"),
+        Target::Snap => home
+            .join(constants::STEAM_SNAP_PATH)
+            .join("common/.local/share/Steam"),
+        _ => return Err(Error::Config(format!("Steam has no {} install", target))),
+    })
+}
+
+// Steam writes config.vdf back out when it exits, so changes made while it's running are lost
+pub fn is_running() -> bool {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return false;
+    };
+    entries
+        .flatten()
+        .filter(|e| {
+            e.file_name()
+                .to_str()
+                .is_some_and(|name| name.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .any(|e| {
+            std::fs::read_to_string(e.path().join("comm"))
+                .is_ok_and(|comm| comm.trim_end() == "steam")
+        })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatTool {
+    pub app_id: String,
+    // The internal name of the tool, from its compatibilitytool.vdf
+    pub name: String,
+}
+
+// config/config.vdf of a Steam install
+#[derive(Debug, Clone)]
+pub struct SteamConfig {
+    pub path: PathBuf,
+    vdf: Object,
+}
+
+impl SteamConfig {
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join("config/config.vdf");
+        if !path.exists() {
+            return Err(Error::NotFound(format!(
+                "Steam config {:?}. Has Steam been run?",
+                path
+            )));
+        }
+        let vdf = read_vdf(&path)?;
+        Ok(Self { path, vdf })
+    }
+
+    pub fn compat_tools(&self) -> Vec<CompatTool> {
+        let Some(mapping) = self.vdf.get_path(&COMPAT_TOOL_MAPPING) else {
+            return Vec::new();
+        };
+        mapping
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                let name = entry.value.as_object()?.get_str("name")?;
+                // Steam leaves entries with an empty name behind when a tool is unset
+                (!name.is_empty()).then(|| CompatTool {
+                    app_id: entry.key.clone(),
+                    name: name.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    pub fn compat_tool(&self, app_id: &str) -> Option<CompatTool> {
+        self.compat_tools().into_iter().find(|t| t.app_id == app_id)
+    }
+
+    // Steam gives the default tool a priority of 75 and tools picked for a game 250
+    pub fn set_compat_tool(&mut self, app_id: &str, name: &str) {
+        let priority = if app_id == "0" { "75" } else { "250" };
+        let mut tool = Object::default();
+        tool.insert("name", Value::String(name.to_string()));
+        tool.insert("config", Value::String(String::new()));
+        tool.insert("priority", Value::String(priority.to_string()));
+        self.vdf
+            .get_path_mut(&COMPAT_TOOL_MAPPING)
+            .insert(app_id, Value::Object(tool));
+    }
+
+    // Returns whether the app had a tool set
+    pub fn unset_compat_tool(&mut self, app_id: &str) -> bool {
+        let had_tool = self.compat_tool(app_id).is_some();
+        self.vdf.get_path_mut(&COMPAT_TOOL_MAPPING).remove(app_id);
+        had_tool
+    }
+
+    // Back up the file on disk and replace it. Returns the path of the backup.
+    pub fn save(&self) -> Result<PathBuf> {
+        let backup = utils::backup(&self.path)?;
+        utils::write_atomic(&self.path, &vdf::to_string(&self.vdf))?;
+        Ok(backup)
+    }
+}
+
+// The internal name Steam knows a compatibility tool by, which is the key of its entry in
+// compatibilitytool.vdf and doesn't have to match the directory name
+pub fn tool_name(tool_directory: &Path) -> Result<String> {
+    let path = tool_directory.join("compatibilitytool.vdf");
+    read_vdf(&path)?
+        .get_path(&["compatibilitytools", "compat_tools"])
+        .and_then(|tools| tools.entries.first())
+        .map(|entry| entry.key.clone())
+        .ok_or(Error::Vdf(format!(
+            "{:?} doesn't declare a compatibility tool",
+            path
+        )))
+}
+
+// Find an installed compatibility tool by its directory or internal name and return the
+// internal name
+pub fn find_tool(compat_directory: &Path, query: &str) -> Option<String> {
+    let tool_directory = compat_directory.join(query);
+    if tool_directory.is_dir() {
+        if let Ok(name) = tool_name(&tool_directory) {
+            return Some(name);
+        }
+    }
+    std::fs::read_dir(compat_directory)
+        .ok()?
+        .flatten()
+        .filter_map(|e| tool_name(&e.path()).ok())
+        .find(|name| name == query)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct App {
+    pub id: String,
+    pub name: String,
+    // The library folder the app is installed in
+    pub library: PathBuf,
+}
+
+// The library folders of a Steam install from steamapps/libraryfolders.vdf, starting with the
+// root itself. Older files list the paths directly instead of in an object per library.
+pub fn library_folders(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut folders = vec![root.to_path_buf()];
+    let path = root.join("steamapps/libraryfolders.vdf");
+    if !path.exists() {
+        return Ok(folders);
+    }
+    let vdf = read_vdf(&path)?;
+    let Some(libraries) = vdf.get_object("libraryfolders") else {
+        return Ok(folders);
+    };
+    for entry in &libraries.entries {
+        if !entry.key.bytes().all(|b| b.is_ascii_digit()) {
+            continue;
+        }
+        let folder = match &entry.value {
+            Value::String(path) => Some(path.as_str()),
+            Value::Object(library) => library.get_str("path"),
+        };
+        if let Some(folder) = folder.map(PathBuf::from) {
+            let same =
+                |f: &PathBuf| f == &folder || f.canonicalize().ok() == folder.canonicalize().ok();
+            if !folders.iter().any(same) {
+                folders.push(folder);
+            }
+        }
+    }
+    Ok(folders)
+}
+
+// Every app with an appmanifest in one of the library folders. Libraries that can't be read,
+// such as unmounted drives, are skipped, as are appmanifests that don't parse.
+pub fn installed_apps(root: &Path) -> Result<Vec<App>> {
+    let mut apps = Vec::new();
+    for library in library_folders(root)? {
+        let Ok(entries) = std::fs::read_dir(library.join("steamapps")) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if !(name.starts_with("appmanifest_") && name.ends_with(".acf")) {
+                continue;
+            }
+            let manifest = match read_vdf(&entry.path()) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    eprintln!("Skipping {:?}: {}", entry.path(), e);
+                    continue;
+                }
+            };
+            let Some(state) = manifest.get_object("AppState") else {
+                continue;
+            };
+            if let (Some(id), Some(name)) = (state.get_str("appid"), state.get_str("name")) {
+                apps.push(App {
+                    id: id.to_string(),
+                    name: name.to_string(),
+                    library: library.clone(),
+                });
+            }
+        }
+    }
+    apps.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(apps)
+}
+
+// Find the app id for an app id or the name of an installed game. Names match exactly
+// (ignoring case) or as part of a single game's name.
+pub fn find_app_id(apps: &[App], query: &str) -> Result<String> {
+    if !query.is_empty() && query.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(query.to_string());
+    }
+    if let Some(app) = apps.iter().find(|a| a.name.eq_ignore_ascii_case(query)) {
+        return Ok(app.id.clone());
+    }
+    let query_lower = query.to_lowercase();
+    let matches: Vec<_> = apps
+        .iter()
+        .filter(|a| a.name.to_lowercase().contains(&query_lower))
+        .collect();
+    match matches.as_slice() {
+        [app] => Ok(app.id.clone()),
+        [] => Err(Error::NotFound(format!("installed game {:?}", query))),
+        _ => Err(Error::Config(format!(
+            "{:?} matches more than one game: {}. Use the app id instead",
+            query,
+            matches
+                .iter()
+                .map(|a| format!("{} ({})", a.name, a.id))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+// A game, or the default for every game when app_id is 0, set to use a compatibility tool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assignment {
+    pub app_id: String,
+    // None when the game isn't installed in any library folder
+    pub app_name: Option<String>,
+    // Internal name of the tool
+    pub tool: String,
+}
+
+impl Display for Assignment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.app_name {
+            Some(name) => write!(f, "{} ({})", name, self.app_id),
+            None if self.app_id == "0" => write!(f, "Default"),
+            None => write!(f, "Unknown app ({})", self.app_id),
+        }
+    }
+}
+
+// Every compatibility tool assignment in a Steam install, named after the installed games.
+// A Steam that has never been run has none.
+pub fn assignments(root: &Path) -> Result<Vec<Assignment>> {
+    let config = match SteamConfig::load(root) {
+        Ok(config) => config,
+        Err(Error::NotFound(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let apps = installed_apps(root)?;
+    Ok(config
+        .compat_tools()
+        .into_iter()
+        .map(|tool| Assignment {
+            app_name: apps
+                .iter()
+                .find(|a| a.id == tool.app_id)
+                .map(|a| a.name.clone()),
+            app_id: tool.app_id,
+            tool: tool.name,
+        })
+        .collect())
+}
+
+// The assignments using the tool installed in tool_directory
+pub fn assigned_to<'a>(
+    assignments: &'a [Assignment],
+    tool_directory: &Path,
+) -> Vec<&'a Assignment> {
+    match tool_name(tool_directory) {
+        Ok(name) => assignments.iter().filter(|a| a.tool == name).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn read_vdf(path: &Path) -> Result<Object> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| Error::io(format!("Failed to read {:?}", path), e))?;
+    vdf::parse(&contents).map_err(|e| match e {
+        Error::Vdf(reason) => Error::Vdf(format!("{:?} {}", path, reason)),
+        e => e,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::steam::{
+        assigned_to, assignments, find_app_id, find_root, find_tool, installed_apps, SteamConfig,
+    };
+    use crate::test_utils::TempDir;
+
+    #[test]
+    fn follows_root_symlink() -> std::io::Result<()> {
+        let home = TempDir::new("steam-symlink");
+        std::fs::create_dir_all(home.join("games/Steam/steamapps"))?;
+        std::fs::create_dir_all(home.join(".local/share/Steam/steamapps"))?;
+        std::fs::create_dir_all(home.join(".steam"))?;
+        std::os::unix::fs::symlink(home.join("games/Steam"), home.join(".steam/root"))?;
+
+        let root = find_root(&home, &home.join(".local/share")).unwrap();
+        assert_eq!(root, home.join("games/Steam").canonicalize()?);
+        Ok(())
+    }
+
+    #[test]
+    fn finds_distro_layouts() -> std::io::Result<()> {
+        let home = TempDir::new("steam-debian");
+        assert!(find_root(&home, &home.join(".local/share")).is_none());
+        std::fs::create_dir_all(home.join(".steam/debian-installation/steamapps"))?;
+
+        let root = find_root(&home, &home.join(".local/share")).unwrap();
+        assert_eq!(
+            root,
+            home.join(".steam/debian-installation").canonicalize()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn can_edit_compat_tools() -> std::io::Result<()> {
+        let root = TempDir::new("steam-config");
+        std::fs::create_dir_all(root.join("config"))?;
+        assert!(SteamConfig::load(&root).is_err());
+        std::fs::write(
+            root.join("config/config.vdf"),
+            "\"InstallConfigStore\"\n{\n\t\"Software\"\n\t{\n\t}\n}\n",
+        )?;
+
+        let mut config = SteamConfig::load(&root).unwrap();
+        assert!(config.compat_tools().is_empty());
+        config.set_compat_tool("440", "GE-Proton8-25");
+        config.set_compat_tool("0", "proton_experimental");
+        config.set_compat_tool("440", "GE-Proton8-26");
+        let backup = config.save().unwrap();
+        assert!(backup.exists());
+        assert!(!std::fs::read_to_string(&backup)?.contains("CompatToolMapping"));
+
+        let mut config = SteamConfig::load(&root).unwrap();
+        assert_eq!(config.compat_tools().len(), 2);
+        assert_eq!(config.compat_tool("440").unwrap().name, "GE-Proton8-26");
+        assert!(config.unset_compat_tool("440"));
+        assert!(!config.unset_compat_tool("440"));
+        assert_eq!(config.compat_tool("0").unwrap().name, "proton_experimental");
+        Ok(())
+    }
+
+    #[test]
+    fn can_find_installed_apps() -> std::io::Result<()> {
+        let root = TempDir::new("steam-apps");
+        let library = root.join("library");
+        std::fs::create_dir_all(root.join("steamapps"))?;
+        std::fs::create_dir_all(library.join("steamapps"))?;
+        std::fs::write(
+            root.join("steamapps/libraryfolders.vdf"),
+            format!(
+                "\"libraryfolders\"\n{{\n\"0\"\n{{\n\"path\" \"{}\"\n}}\n\"1\"\n{{\n\"path\" \"{}\"\n}}\n}}\n",
+                root.display(),
+                library.display()
+            ),
+        )?;
+        let manifest = |id: &str, name: &str| {
+            format!(
+                "\"AppState\"\n{{\n\"appid\" \"{}\"\n\"name\" \"{}\"\n}}\n",
+                id, name
+            )
+        };
+        std::fs::write(
+            root.join("steamapps/appmanifest_440.acf"),
+            manifest("440", "Team Fortress 2"),
+        )?;
+        std::fs::write(
+            library.join("steamapps/appmanifest_1091500.acf"),
+            manifest("1091500", "Cyberpunk 2077"),
+        )?;
+        std::fs::write(
+            library.join("steamapps/appmanifest_220.acf"),
+            manifest("220", "Half-Life 2"),
+        )?;
+        std::fs::write(
+            library.join("steamapps/appmanifest_380.acf"),
+            manifest("380", "Half-Life 2: Episode One"),
+        )?;
+
+        let apps = installed_apps(&root).unwrap();
+        assert_eq!(apps.len(), 4);
+        assert_eq!(apps[0].name, "Cyberpunk 2077");
+        assert_eq!(apps[0].library, library);
+        assert_eq!(find_app_id(&apps, "cyberpunk").unwrap(), "1091500");
+        assert_eq!(find_app_id(&apps, "half-life 2").unwrap(), "220");
+        assert_eq!(find_app_id(&apps, "730").unwrap(), "730");
+        assert!(find_app_id(&apps, "Half-Life").is_err());
+        assert!(find_app_id(&apps, "Portal").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn can_find_tools_by_internal_name() -> std::io::Result<()> {
+        let compat_directory = TempDir::new("steam-tools");
+        let tool = compat_directory.join("GE-Proton8-25");
+        std::fs::create_dir_all(&tool)?;
+        std::fs::create_dir_all(compat_directory.join("broken"))?;
+        std::fs::write(
+            tool.join("compatibilitytool.vdf"),
+            "\"compatibilitytools\"\n{\n  \"compat_tools\"\n  {\n    \"GE-Proton8-25-custom\" // Internal name of this tool\n    {\n      \"install_path\" \".\"\n    }\n  }\n}\n",
+        )?;
+
+        assert_eq!(
+            find_tool(&compat_directory, "GE-Proton8-25").unwrap(),
+            "GE-Proton8-25-custom"
+        );
+        assert_eq!(
+            find_tool(&compat_directory, "GE-Proton8-25-custom").unwrap(),
+            "GE-Proton8-25-custom"
+        );
+        assert!(find_tool(&compat_directory, "broken").is_none());
+        assert!(find_tool(&compat_directory, "proton_experimental").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn can_find_assignments() -> std::io::Result<()> {
+        let root = TempDir::new("steam-assignments");
+        assert!(assignments(&root).unwrap().is_empty());
+        let tool = root.join("compatibilitytools.d/GE-Proton8-25");
+        std::fs::create_dir_all(&tool)?;
+        std::fs::create_dir_all(root.join("config"))?;
+        std::fs::create_dir_all(root.join("steamapps"))?;
+        std::fs::write(
+            tool.join("compatibilitytool.vdf"),
+            "\"compatibilitytools\" { \"compat_tools\" { \"GE-Proton8-25\" { } } }",
+        )?;
+        std::fs::write(
+            root.join("steamapps/appmanifest_440.acf"),
+            "\"AppState\" { \"appid\" \"440\" \"name\" \"Team Fortress 2\" }",
+        )?;
+        // A truncated appmanifest only loses that game's name
+        std::fs::write(
+            root.join("steamapps/appmanifest_730.acf"),
+            "\"AppState\" { \"appid\" \"730\" \"name\"",
+        )?;
+        std::fs::write(root.join("config/config.vdf"), "\"InstallConfigStore\" { }")?;
+        let mut config = SteamConfig::load(&root).unwrap();
+        config.set_compat_tool("0", "GE-Proton8-25");
+        config.set_compat_tool("440", "GE-Proton8-25");
+        config.set_compat_tool("730", "GE-Proton8-25");
+        config.set_compat_tool("220", "proton_experimental");
+        config.save().unwrap();
+
+        let assignments = assignments(&root).unwrap();
+        let used_by: Vec<String> = assigned_to(&assignments, &tool)
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        assert_eq!(
+            used_by,
+            vec!["Default", "Team Fortress 2 (440)", "Unknown app (730)"]
+        );
+        assert!(assigned_to(&assignments, &root.join("missing")).is_empty());
+        Ok(())
+    }
+}
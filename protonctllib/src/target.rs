@@ -0,0 +1,254 @@
+// Which install of the client a build goes to: the distro package, the flatpak or the snap of
+// Steam or Lutris, Heroic, which keeps its own copies of both, or the wine runners of Bottles
+use crate::constants;
+use crate::error::{Error, Result};
+use crate::install_type::InstallType;
+use crate::source::Source;
+use crate::steam;
+use crate::utils;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::path::Path;
+
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Target {
+    #[default]
+    Native,
+    Flatpak,
+    Snap,
+    Heroic,
+    HeroicFlatpak,
+    Bottles,
+    BottlesFlatpak,
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Native => write!(f, "native"),
+            Target::Flatpak => write!(f, "flatpak"),
+            Target::Snap => write!(f, "snap"),
+            Target::Heroic => write!(f, "heroic"),
+            Target::HeroicFlatpak => write!(f, "heroic-flatpak"),
+            Target::Bottles => write!(f, "bottles"),
+            Target::BottlesFlatpak => write!(f, "bottles-flatpak"),
+        }
+    }
+}
+
+impl Target {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "native" => Some(Target::Native),
+            "flatpak" => Some(Target::Flatpak),
+            "snap" => Some(Target::Snap),
+            "heroic" => Some(Target::Heroic),
+            "heroic-flatpak" => Some(Target::HeroicFlatpak),
+            "bottles" => Some(Target::Bottles),
+            "bottles-flatpak" => Some(Target::BottlesFlatpak),
+            _ => None,
+        }
+    }
+}
+
+// The targets a source can be installed to
+pub fn supported(source: &Source) -> Vec<Target> {
+    match source {
+        Source::Builtin(InstallType::Proton) => vec![
+            Target::Native,
+            Target::Flatpak,
+            Target::Snap,
+            Target::Heroic,
+            Target::HeroicFlatpak,
+        ],
+        Source::Builtin(InstallType::Wine) => vec![
+            Target::Native,
+            Target::Flatpak,
+            Target::Heroic,
+            Target::HeroicFlatpak,
+            Target::Bottles,
+            Target::BottlesFlatpak,
+        ],
+        Source::Builtin(InstallType::ULWGL) => vec![Target::Native],
+        Source::Custom(source) if source.flatpak_directory.is_some() => {
+            vec![Target::Native, Target::Flatpak]
+        }
+        Source::Custom(_) => vec![Target::Native],
+    }
+}
+
+// The targets whose client is installed for this user
+pub fn detect(source: &Source) -> Result<Vec<Target>> {
+    let home = dirs::home_dir().ok_or(Error::HomeDirMissing)?;
+    let data_dir = dirs::data_dir().ok_or(Error::HomeDirMissing)?;
+    let config_dir = dirs::config_dir().ok_or(Error::HomeDirMissing)?;
+    detect_in(source, &home, &data_dir, &config_dir)
+}
+
+fn detect_in(
+    source: &Source,
+    home: &Path,
+    data_dir: &Path,
+    config_dir: &Path,
+) -> Result<Vec<Target>> {
+    let flatpak = |id: &str| home.join(".var/app").join(id).is_dir();
+    let heroic = [
+        (Target::Heroic, config_dir.join("heroic").is_dir()),
+        (Target::HeroicFlatpak, flatpak(constants::HEROIC_FLATPAK_ID)),
+    ];
+    let detected = match source {
+        Source::Builtin(InstallType::Proton) => [
+            (Target::Native, steam::find_root(home, data_dir).is_some()),
+            (Target::Flatpak, flatpak(constants::STEAM_FLATPAK_ID)),
+            (Target::Snap, home.join(constants::STEAM_SNAP_PATH).is_dir()),
+        ]
+        .into_iter()
+        .chain(heroic)
+        .collect(),
+        Source::Builtin(InstallType::Wine) => [
+            (Target::Native, data_dir.join("lutris").is_dir()),
+            (Target::Flatpak, flatpak(constants::LUTRIS_FLATPAK_ID)),
+        ]
+        .into_iter()
+        .chain(heroic)
+        .chain([
+            (Target::Bottles, data_dir.join("bottles").is_dir()),
+            (
+                Target::BottlesFlatpak,
+                flatpak(constants::BOTTLES_FLATPAK_ID),
+            ),
+        ])
+        .collect(),
+        Source::Builtin(InstallType::ULWGL) => vec![(Target::Native, true)],
+        // Custom sources only say where builds go, so look for those directories
+        Source::Custom(source) => {
+            let mut detected = vec![(
+                Target::Native,
+                utils::home_path(&source.directory)?.is_dir(),
+            )];
+            if let Some(directory) = &source.flatpak_directory {
+                detected.push((Target::Flatpak, utils::home_path(directory)?.is_dir()));
+            }
+            detected
+        }
+    };
+    Ok(detected
+        .into_iter()
+        .filter_map(|(target, found)| found.then_some(target))
+        .collect())
+}
+
+// The targets to use for the --target values: the ones named, every detected one for all, or
+// the only detected one when nothing was asked for. Nothing detected falls back to native.
+pub fn select(source: &Source, requested: &[&str]) -> Result<Vec<Target>> {
+    select_in(source, requested, &supported(source), |target| {
+        format!("{} can't be installed to {}", source, target)
+    })
+}
+
+// The one target to use for a command that edits a launcher's own config, chosen from the
+// targets that launcher has
+pub fn select_one(
+    source: &Source,
+    requested: &[&str],
+    launcher: &str,
+    targets: &[Target],
+) -> Result<Target> {
+    let selected = select_in(source, requested, targets, |target| {
+        format!("{} has no {} install", launcher, target)
+    })?;
+    match selected[..] {
+        [target] => Ok(target),
+        _ => Err(Error::AmbiguousTarget(selected)),
+    }
+}
+
+fn select_in(
+    source: &Source,
+    requested: &[&str],
+    allowed: &[Target],
+    unsupported: impl Fn(Target) -> String,
+) -> Result<Vec<Target>> {
+    let detect = || -> Result<Vec<Target>> {
+        let mut detected = detect(source)?;
+        detected.retain(|t| allowed.contains(t));
+        Ok(detected)
+    };
+    let mut targets = Vec::new();
+    for name in requested {
+        if *name == "all" {
+            targets.extend(detect()?);
+            continue;
+        }
+        let target = Target::from_name(name).ok_or(Error::Config(format!(
+            "Unknown target {:?}. Expected native, flatpak, snap, heroic, heroic-flatpak, bottles, bottles-flatpak or all",
+            name
+        )))?;
+        if !allowed.contains(&target) {
+            return Err(Error::Config(unsupported(target)));
+        }
+        targets.push(target);
+    }
+    if requested.is_empty() {
+        let detected = detect()?;
+        if detected.len() > 1 {
+            return Err(Error::AmbiguousTarget(detected));
+        }
+        targets = detected;
+    }
+    targets.sort();
+    targets.dedup();
+    if targets.is_empty() {
+        targets.push(Target::Native);
+    }
+    Ok(targets)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::install_type::InstallType;
+    use crate::source::Source;
+    use crate::target::{detect_in, supported, Target};
+    use crate::test_utils::TempDir;
+
+    #[test]
+    fn can_detect_clients() -> std::io::Result<()> {
+        let home = TempDir::new("target-detect");
+        let data_dir = home.join(".local/share");
+        let config_dir = home.join(".config");
+        let proton = Source::Builtin(InstallType::Proton);
+        let wine = Source::Builtin(InstallType::Wine);
+        let detect = |source| detect_in(source, &home, &data_dir, &config_dir).unwrap();
+        assert!(detect(&proton).is_empty());
+
+        std::fs::create_dir_all(home.join(".var/app/com.valvesoftware.Steam"))?;
+        std::fs::create_dir_all(home.join("snap/steam"))?;
+        std::fs::create_dir_all(data_dir.join("lutris"))?;
+        assert_eq!(detect(&proton), vec![Target::Flatpak, Target::Snap]);
+        assert_eq!(detect(&wine), vec![Target::Native]);
+
+        std::fs::create_dir_all(data_dir.join("Steam/steamapps"))?;
+        assert_eq!(detect(&proton).len(), 3);
+
+        // Heroic takes both proton and wine builds
+        std::fs::create_dir_all(config_dir.join("heroic"))?;
+        std::fs::create_dir_all(home.join(".var/app/com.heroicgameslauncher.hgl"))?;
+        assert_eq!(detect(&proton).len(), 5);
+        assert_eq!(
+            detect(&wine),
+            vec![Target::Native, Target::Heroic, Target::HeroicFlatpak]
+        );
+
+        std::fs::create_dir_all(data_dir.join("bottles"))?;
+        assert_eq!(detect(&wine).len(), 4);
+        assert_eq!(detect(&proton).len(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn ulwgl_is_native_only() {
+        let ulwgl = Source::Builtin(InstallType::ULWGL);
+        assert_eq!(supported(&ulwgl), vec![Target::Native]);
+    }
+}
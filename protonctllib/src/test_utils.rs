@@ -0,0 +1,33 @@
+// Helpers shared by the unit tests
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+// A directory in the system temp directory that is deleted again when the test finishes,
+// whether it passes or not
+pub struct TempDir {
+    pub path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(test: &str) -> Self {
+        let mut path = std::env::temp_dir();
+        path.push(format!("protonctl-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
@@ -1,35 +1,96 @@
-use crate::constants;
+use crate::error::{Error, Result};
 use sha2::{Digest, Sha512};
+use std::path::{Path, PathBuf};
 
-use anyhow::Context;
 use dirs::home_dir;
 
-pub fn get_download_directory_safe() -> anyhow::Result<std::path::PathBuf> {
-    let mut download_dir =
-        home_dir().ok_or(anyhow::anyhow!("Couldn't get users home directory"))?;
-    download_dir.push(constants::DOWNLOAD_PATH);
-    if !download_dir.exists() {
-        std::fs::create_dir_all(&download_dir)?;
-        Ok(download_dir)
-    } else {
-        Ok(download_dir)
+// Resolve a configured path. Absolute paths are kept, anything else (with or without a leading
+// ~/) is relative to the home directory.
+pub fn home_path(path: &Path) -> Result<PathBuf> {
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+    let mut home = home_dir().ok_or(Error::HomeDirMissing)?;
+    home.push(path.strip_prefix("~").unwrap_or(path));
+    Ok(home)
+}
+
+// Create path if it doesn't exist and return it
+pub fn create_directory_safe(path: &Path) -> Result<PathBuf> {
+    if !path.exists() {
+        std::fs::create_dir_all(path)
+            .map_err(|e| Error::io(format!("Failed to create directory: {:?}", path), e))?;
     }
+    Ok(path.to_path_buf())
 }
 
-pub fn remove_entry(file: &std::path::PathBuf) -> anyhow::Result<()> {
-    if file.is_dir() {
-        if std::fs::remove_dir_all(file).is_err() {
-            eprintln!("Failed to remove directory {:?}", file);
+// Copy a file to <name>.<unix time>.bak next to it before it's modified, so every earlier
+// version is kept. Backups made in the same second get a counter, <name>.<unix time>.<n>.bak,
+// rather than replacing each other. Returns the path of the backup.
+pub fn backup(path: &Path) -> Result<PathBuf> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let mut source = std::fs::File::open(path)
+        .map_err(|e| Error::io(format!("Failed to back up {:?}", path), e))?;
+    let mut count = 0;
+    loop {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        match count {
+            0 => name.push(format!(".{}.bak", now)),
+            n => name.push(format!(".{}.{}.bak", now, n)),
+        }
+        let backup = path.with_file_name(name);
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&backup)
+        {
+            Ok(mut file) => {
+                std::io::copy(&mut source, &mut file)
+                    .map_err(|e| Error::io(format!("Failed to back up {:?}", path), e))?;
+                return Ok(backup);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => count += 1,
+            Err(e) => return Err(Error::io(format!("Failed to back up {:?}", path), e)),
         }
-    } else if std::fs::remove_file(file).is_err() {
-        eprintln!("Failed to remove file {:?}", file);
     }
-    Ok(())
 }
 
-pub fn remove_all_in(path: &std::path::PathBuf) -> anyhow::Result<()> {
-    let entries = std::fs::read_dir(path).context("Failed to read directory")?;
+// Write to a temporary file first so an interrupted write can't leave path half written
+pub fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    let tmp_path = path.with_file_name(name);
+    std::fs::write(&tmp_path, contents)
+        .map_err(|e| Error::io(format!("Failed to write {:?}", tmp_path), e))?;
+    std::fs::rename(&tmp_path, path)
+        .map_err(|e| Error::io(format!("Failed to replace {:?}", path), e))
+}
+
+// Remove a file or a directory and everything in it. Symlinks are removed, not followed.
+pub fn remove_entry(file: &std::path::PathBuf) -> Result<()> {
+    let result = if file.is_dir() {
+        std::fs::remove_dir_all(file)
+    } else {
+        std::fs::remove_file(file)
+    };
+    result.map_err(|e| Error::io(format!("Failed to remove {:?}", file), e))
+}
+
+pub fn remove_all_in(path: &std::path::PathBuf) -> Result<()> {
+    remove_all_in_except(path, &[])
+}
+
+// Same as remove_all_in but leaves entries named in keep alone
+pub fn remove_all_in_except(path: &std::path::PathBuf, keep: &[&str]) -> Result<()> {
+    let entries = std::fs::read_dir(path)
+        .map_err(|e| Error::io(format!("Failed to read directory: {:?}", path), e))?;
     for entry in entries.flatten() {
+        if keep.iter().any(|k| entry.file_name() == *k) {
+            continue;
+        }
         let entry_path = entry.path();
         if let Ok(file_type) = entry.file_type() {
             if file_type.is_dir() {
@@ -46,17 +107,161 @@ pub fn remove_all_in(path: &std::path::PathBuf) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn check_sha(tar: &std::path::PathBuf, sha: &str) -> anyhow::Result<bool> {
-    let mut file = std::fs::OpenOptions::new()
+// Bytes taken up by the files under path. Symlinks aren't followed and anything unreadable
+// counts as empty.
+pub fn directory_size(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| directory_size(&entry.path()))
+                .sum()
+        })
+        .unwrap_or_default()
+}
+
+// Match name against a glob pattern where * matches any run of characters and ? any one character
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last * and the name position it was tried at, for backtracking
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+// Pick the line for file_name out of a checksum file listing several files. Files with a single
+// line are returned as is.
+pub fn checksum_for<'a>(contents: &'a str, file_name: &str) -> &'a str {
+    contents
+        .lines()
+        .find(|line| {
+            line.split_whitespace()
+                .nth(1)
+                .is_some_and(|name| name.trim_start_matches('*') == file_name)
+        })
+        .unwrap_or(contents)
+}
+
+pub fn check_sha(tar: &std::path::PathBuf, sha: &str) -> Result<bool> {
+    compare_sha(&sha512(tar)?, sha)
+}
+
+// Compare a computed hash against the contents of a sha512sum file
+pub fn compare_sha(hash: &str, sha: &str) -> Result<bool> {
+    match sha.get(0..128) {
+        Some(u) => Ok(u == hash),
+        None => Err(Error::InvalidChecksum(sha.to_string())),
+    }
+}
+
+pub fn sha512(file: &std::path::PathBuf) -> Result<String> {
+    let mut reader = std::fs::OpenOptions::new()
         .read(true)
-        .open(tar)
-        .context(format!("Failed to open compressed file: {:?}", tar))?;
+        .open(file)
+        .map_err(|e| Error::io(format!("Failed to open compressed file: {:?}", file), e))?;
     let mut hasher = Sha512::new();
-    std::io::copy(&mut file, &mut hasher).context("Failed to copy file contents to hasher")?;
-    let final_hash = format!("{:x}", hasher.finalize());
+    std::io::copy(&mut reader, &mut hasher)
+        .map_err(|e| Error::io("Failed to copy file contents to hasher", e))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
 
-    match sha.get(0..128) {
-        Some(u) => Ok(u == final_hash),
-        None => Err(anyhow::anyhow!("Failed to get sha slice")),
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::TempDir;
+    use crate::utils::{backup, checksum_for, directory_size, matches_pattern, remove_entry};
+
+    #[test]
+    fn can_measure_directories() -> std::io::Result<()> {
+        let dir = TempDir::new("size");
+        std::fs::create_dir_all(dir.join("files/bin"))?;
+        std::fs::write(dir.join("proton"), [0; 100])?;
+        std::fs::write(dir.join("files/bin/wine"), [0; 28])?;
+        std::os::unix::fs::symlink("/usr", dir.join("files/usr"))?;
+        assert_eq!(directory_size(&dir), 128 + "/usr".len() as u64);
+        assert_eq!(directory_size(&dir.join("missing")), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn backups_in_the_same_second_are_all_kept() -> std::io::Result<()> {
+        let dir = TempDir::new("backup");
+        let path = dir.join("config.vdf");
+        let mut backups = Vec::new();
+        for contents in ["first", "second", "third"] {
+            std::fs::write(&path, contents)?;
+            backups.push(backup(&path).unwrap());
+        }
+        let contents: Vec<String> = backups
+            .iter()
+            .map(std::fs::read_to_string)
+            .collect::<std::io::Result<_>>()?;
+        assert_eq!(contents, ["first", "second", "third"]);
+        Ok(())
+    }
+
+    #[test]
+    fn remove_entry_reports_failures() -> std::io::Result<()> {
+        let dir = TempDir::new("remove-entry");
+        std::fs::create_dir_all(dir.join("build/bin"))?;
+        std::fs::write(dir.join("build.tar.gz"), "")?;
+        std::os::unix::fs::symlink(dir.join("build"), dir.join("link"))?;
+        remove_entry(&dir.join("link")).unwrap();
+        assert!(dir.join("build/bin").is_dir());
+        remove_entry(&dir.join("build")).unwrap();
+        remove_entry(&dir.join("build.tar.gz")).unwrap();
+        assert!(remove_entry(&dir.join("build")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn can_match_patterns() {
+        assert!(matches_pattern("*.tar.gz", "GE-Proton8-25.tar.gz"));
+        assert!(matches_pattern(
+            "wine-*-staging-tkg-amd64.tar.xz",
+            "wine-9.0-staging-tkg-amd64.tar.xz"
+        ));
+        assert!(matches_pattern("GE-Proton?-*", "GE-Proton8-25"));
+        assert!(!matches_pattern("*.tar.gz", "GE-Proton8-25.sha512sum"));
+        assert!(!matches_pattern(
+            "wine-*-amd64.tar.xz",
+            "wine-9.0-staging-tkg-x86.tar.xz"
+        ));
+    }
+
+    #[test]
+    fn can_find_checksum_lines() {
+        let sums = "aaaa  wine-9.0-x86.tar.xz\nbbbb  wine-9.0-amd64.tar.xz\n";
+        assert_eq!(
+            checksum_for(sums, "wine-9.0-amd64.tar.xz"),
+            "bbbb  wine-9.0-amd64.tar.xz"
+        );
+        assert_eq!(checksum_for("cccc\n", "wine-9.0-amd64.tar.xz"), "cccc\n");
     }
 }
@@ -0,0 +1,427 @@
+// Reading and writing Valve's text KeyValues format, used by config.vdf, libraryfolders.vdf and
+// the appmanifest files. Key order, duplicate keys and platform conditionals are kept so files
+// round trip unchanged apart from whitespace and comments.
+use crate::error::{Error, Result};
+use std::fmt::Write;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    Object(Object),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Object {
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub key: String,
+    pub value: Value,
+    // A platform conditional such as [$WIN32] following the value
+    pub condition: Option<String>,
+}
+
+impl Entry {
+    pub fn new(key: &str, value: Value) -> Self {
+        Self {
+            key: key.to_string(),
+            value,
+            condition: None,
+        }
+    }
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            Value::Object(_) => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&Object> {
+        match self {
+            Value::Object(o) => Some(o),
+            Value::String(_) => None,
+        }
+    }
+}
+
+impl Object {
+    // Keys are case insensitive, as they are in Steam
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.entries
+            .iter()
+            .find(|e| e.key.eq_ignore_ascii_case(key))
+            .map(|e| &e.value)
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key)?.as_str()
+    }
+
+    pub fn get_object(&self, key: &str) -> Option<&Object> {
+        self.get(key)?.as_object()
+    }
+
+    // Follow a path of nested objects
+    pub fn get_path(&self, path: &[&str]) -> Option<&Object> {
+        path.iter()
+            .try_fold(self, |object, key| object.get_object(key))
+    }
+
+    // Follow a path of nested objects, creating any that are missing. A string in the way is
+    // replaced.
+    pub fn get_path_mut(&mut self, path: &[&str]) -> &mut Object {
+        path.iter().fold(self, |object, key| {
+            let index = match object
+                .entries
+                .iter()
+                .position(|e| e.key.eq_ignore_ascii_case(key))
+            {
+                Some(index) => index,
+                None => {
+                    object
+                        .entries
+                        .push(Entry::new(key, Value::Object(Object::default())));
+                    object.entries.len() - 1
+                }
+            };
+            let value = &mut object.entries[index].value;
+            if let Value::String(_) = value {
+                *value = Value::Object(Object::default());
+            }
+            match value {
+                Value::Object(o) => o,
+                Value::String(_) => unreachable!(),
+            }
+        })
+    }
+
+    // Replace the value of the first entry with this key or add it at the end
+    pub fn insert(&mut self, key: &str, value: Value) {
+        match self
+            .entries
+            .iter_mut()
+            .find(|e| e.key.eq_ignore_ascii_case(key))
+        {
+            Some(entry) => entry.value = value,
+            None => self.entries.push(Entry::new(key, value)),
+        }
+    }
+
+    // Remove every entry with this key, returning the first
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        let index = self
+            .entries
+            .iter()
+            .position(|e| e.key.eq_ignore_ascii_case(key))?;
+        let removed = self.entries.remove(index);
+        self.entries.retain(|e| !e.key.eq_ignore_ascii_case(key));
+        Some(removed.value)
+    }
+}
+
+pub fn parse(contents: &str) -> Result<Object> {
+    let mut parser = Parser {
+        chars: contents.chars().peekable(),
+        line: 1,
+    };
+    let object = parser.object(false)?;
+    Ok(object)
+}
+
+// Write in the layout Steam uses: tab indentation and two tabs between keys and values
+pub fn to_string(object: &Object) -> String {
+    let mut out = String::new();
+    write_object(&mut out, object, 0);
+    out
+}
+
+fn write_object(out: &mut String, object: &Object, depth: usize) {
+    let indent = "\t".repeat(depth);
+    for entry in &object.entries {
+        let key = escape(&entry.key);
+        match &entry.value {
+            Value::String(s) => {
+                let _ = write!(out, "{}\"{}\"\t\t\"{}\"", indent, key, escape(s));
+            }
+            Value::Object(o) => {
+                let _ = writeln!(out, "{}\"{}\"", indent, key);
+                let _ = writeln!(out, "{}{{", indent);
+                write_object(out, o, depth + 1);
+                let _ = write!(out, "{}}}", indent);
+            }
+        }
+        match &entry.condition {
+            Some(condition) => {
+                let _ = writeln!(out, " {}", condition);
+            }
+            None => out.push('\n'),
+        }
+    }
+}
+
+// The inverse of the escapes the parser reads, so written strings parse back the same
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    String(String),
+    Open,
+    Close,
+}
+
+impl Parser<'_> {
+    fn error(&self, reason: &str) -> Error {
+        Error::Vdf(format!("line {}: {}", self.line, reason))
+    }
+
+    // Read key value pairs until the closing brace, or the end of the input at the top level
+    fn object(&mut self, nested: bool) -> Result<Object> {
+        let mut object = Object::default();
+        loop {
+            let key = match self.token()? {
+                Some(Token::String(key)) => key,
+                Some(Token::Close) if nested => return Ok(object),
+                None if !nested => return Ok(object),
+                None => return Err(self.error("unexpected end of file, expected '}'")),
+                Some(token) => {
+                    return Err(self.error(&format!("expected a key, found {:?}", token)))
+                }
+            };
+            let value = match self.token()? {
+                Some(Token::String(value)) => Value::String(value),
+                Some(Token::Open) => Value::Object(self.object(true)?),
+                _ => return Err(self.error(&format!("expected a value for {:?}", key))),
+            };
+            let condition = self.conditional();
+            object.entries.push(Entry {
+                key,
+                value,
+                condition,
+            });
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.chars.peek().copied() {
+            if c == '\n' {
+                self.line += 1;
+            }
+            if c.is_whitespace() {
+                self.chars.next();
+            } else if c == '/' {
+                // Comments run to the end of the line
+                let mut lookahead = self.chars.clone();
+                lookahead.next();
+                if lookahead.peek() != Some(&'/') {
+                    return;
+                }
+                while self.chars.peek().is_some_and(|c| *c != '\n') {
+                    self.chars.next();
+                }
+            } else {
+                return;
+            }
+        }
+    }
+
+    // A platform conditional such as [$WIN32] or [!$OSX] after a value, brackets included
+    fn conditional(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        if self.chars.peek() != Some(&'[') {
+            return None;
+        }
+        let mut condition = String::new();
+        for c in self.chars.by_ref() {
+            condition.push(c);
+            if c == ']' {
+                break;
+            }
+        }
+        Some(condition)
+    }
+
+    fn token(&mut self) -> Result<Option<Token>> {
+        self.skip_whitespace();
+        let c = match self.chars.next() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        Ok(Some(match c {
+            '{' => Token::Open,
+            '}' => Token::Close,
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match self.chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match self.chars.next() {
+                            Some('n') => s.push('\n'),
+                            Some('t') => s.push('\t'),
+                            Some(c) => s.push(c),
+                            None => return Err(self.error("unterminated string")),
+                        },
+                        Some(c) => {
+                            if c == '\n' {
+                                self.line += 1;
+                            }
+                            s.push(c)
+                        }
+                        None => return Err(self.error("unterminated string")),
+                    }
+                }
+                Token::String(s)
+            }
+            c => {
+                let mut s = String::from(c);
+                while let Some(c) = self
+                    .chars
+                    .peek()
+                    .copied()
+                    .filter(|c| !c.is_whitespace() && !matches!(c, '{' | '}' | '"'))
+                {
+                    s.push(c);
+                    self.chars.next();
+                }
+                Token::String(s)
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vdf::{parse, to_string, Object, Value};
+
+    const CONFIG: &str = r#""InstallConfigStore"
+{
+	"Software"
+	{
+		"Valve"
+		{
+			"Steam"
+			{
+				// Set by the client
+				"AutoUpdateWindowEnabled"		"0"
+				"CompatToolMapping"
+				{
+					"0"
+					{
+						"name"		"proton_experimental"
+						"config"		""
+						"priority"		"75"
+					}
+				}
+				"Path"		"C:\\Program Files\\Steam" [$WIN32]
+				"Proxy"
+				{
+					"Host"		""
+				} [!$OSX]
+			}
+		}
+	}
+}
+"#;
+
+    #[test]
+    fn can_parse_and_write() {
+        let vdf = parse(CONFIG).unwrap();
+        let steam = vdf
+            .get_path(&["InstallConfigStore", "Software", "Valve", "steam"])
+            .unwrap();
+        assert_eq!(steam.get_str("autoupdatewindowenabled"), Some("0"));
+        assert_eq!(steam.get_str("Path"), Some("C:\\Program Files\\Steam"));
+        let default = steam.get_path(&["CompatToolMapping", "0"]).unwrap();
+        assert_eq!(default.get_str("name"), Some("proton_experimental"));
+        assert_eq!(default.get_str("config"), Some(""));
+
+        // Round trips apart from the comment
+        let written = to_string(&vdf);
+        assert_eq!(parse(&written).unwrap(), vdf);
+        assert!(written.contains("\t\t\t\t\"CompatToolMapping\"\n\t\t\t\t{\n"));
+        assert!(written.contains("\"Path\"\t\t\"C:\\\\Program Files\\\\Steam\" [$WIN32]\n"));
+        assert!(written
+            .contains("\"Proxy\"\n\t\t\t\t{\n\t\t\t\t\t\"Host\"\t\t\"\"\n\t\t\t\t} [!$OSX]\n"));
+    }
+
+    #[test]
+    fn can_edit_nested_objects() {
+        let mut vdf = parse(CONFIG).unwrap();
+        let mapping = vdf.get_path_mut(&[
+            "InstallConfigStore",
+            "Software",
+            "Valve",
+            "Steam",
+            "CompatToolMapping",
+        ]);
+        let mut tool = Object::default();
+        tool.insert("name", Value::String(String::from("GE-Proton8-25")));
+        mapping.insert("440", Value::Object(tool));
+        assert!(mapping.remove("0").is_some());
+        assert!(mapping.remove("0").is_none());
+
+        let vdf = parse(&to_string(&vdf)).unwrap();
+        let mapping = vdf
+            .get_path(&[
+                "InstallConfigStore",
+                "Software",
+                "Valve",
+                "Steam",
+                "CompatToolMapping",
+            ])
+            .unwrap();
+        assert_eq!(mapping.entries.len(), 1);
+        assert_eq!(
+            mapping.get_path(&["440"]).unwrap().get_str("name"),
+            Some("GE-Proton8-25")
+        );
+
+        let mut empty = Object::default();
+        empty
+            .get_path_mut(&["a", "b"])
+            .insert("c", Value::String(String::from("d")));
+        assert_eq!(
+            to_string(&empty),
+            "\"a\"\n{\n\t\"b\"\n\t{\n\t\t\"c\"\t\t\"d\"\n\t}\n}\n"
+        );
+    }
+
+    #[test]
+    fn round_trips_escapes() {
+        let mut vdf = Object::default();
+        let value = "launch \"%command%\"\n\tC:\\Games";
+        vdf.insert("LaunchOptions", Value::String(String::from(value)));
+        let written = to_string(&vdf);
+        assert!(
+            written.contains("%command%\\\"\\n\\tC:\\\\Games"),
+            "{}",
+            written
+        );
+        assert_eq!(
+            parse(&written).unwrap().get_str("LaunchOptions"),
+            Some(value)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        assert!(parse("\"a\"\n{\n\"b\" \"c\"\n").is_err());
+        assert!(parse("\"a\" }").is_err());
+        assert!(parse("\"a\" \"b").is_err());
+        assert!(parse("").unwrap().entries.is_empty());
+    }
+}
@@ -0,0 +1,222 @@
+// Parsing and ordering of release tags and the directories they install to
+use crate::install_type::InstallType;
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Eq)]
+pub struct Version {
+    pub install_type: InstallType,
+    pub major: u32,
+    pub minor: u32,
+    pub suffix: Option<String>,
+}
+
+impl Version {
+    // Parse a release tag such as GE-Proton8-25, GE-Proton9-1-rtsp or ULWGL-Proton-0.1-RC3
+    pub fn parse(install_type: InstallType, tag: &str) -> Option<Self> {
+        let (major, minor, suffix) = match install_type {
+            InstallType::Proton | InstallType::Wine => {
+                let rest = tag.strip_prefix("GE-Proton")?;
+                let (major, rest) = rest.split_once('-')?;
+                split_minor(major, rest)?
+            }
+            InstallType::ULWGL => {
+                let rest = tag.strip_prefix("ULWGL-Proton-")?;
+                let (major, rest) = rest.split_once('.')?;
+                split_minor(major, rest)?
+            }
+        };
+        Some(Self {
+            install_type,
+            major,
+            minor,
+            suffix,
+        })
+    }
+
+    // Parse the name of an installed directory such as lutris-GE-Proton8-26-x86_64, or
+    // wine-ge-proton8-26 as Bottles names them
+    pub fn from_directory(install_type: InstallType, name: &str) -> Option<Self> {
+        match install_type {
+            InstallType::Wine => {
+                if let Some(rest) = name.strip_prefix("wine-ge-proton") {
+                    return Self::parse(install_type, &format!("GE-Proton{}", rest));
+                }
+                let tag = name.strip_prefix("lutris-")?.strip_suffix("-x86_64")?;
+                Self::parse(install_type, tag)
+            }
+            InstallType::Proton | InstallType::ULWGL => Self::parse(install_type, name),
+        }
+    }
+
+    pub fn tag(&self) -> String {
+        let mut tag = match self.install_type {
+            InstallType::Proton | InstallType::Wine => {
+                format!("GE-Proton{}-{}", self.major, self.minor)
+            }
+            InstallType::ULWGL => format!("ULWGL-Proton-{}.{}", self.major, self.minor),
+        };
+        if let Some(suffix) = &self.suffix {
+            tag.push('-');
+            tag.push_str(suffix);
+        }
+        tag
+    }
+
+    pub fn directory_name(&self) -> String {
+        directory_name(self.install_type, &self.tag())
+    }
+}
+
+// Name of the directory a release tag unpacks to inside the compatibility directory
+pub fn directory_name(install_type: InstallType, tag: &str) -> String {
+    match install_type {
+        InstallType::Wine => format!("lutris-{}-x86_64", tag),
+        InstallType::Proton | InstallType::ULWGL => tag.to_string(),
+    }
+}
+
+// Name Bottles expects for a wine release, e.g. wine-ge-proton8-26
+pub fn bottles_directory_name(tag: &str) -> String {
+    format!("wine-{}", tag.to_lowercase())
+}
+
+fn split_minor(major: &str, rest: &str) -> Option<(u32, u32, Option<String>)> {
+    let major = major.parse().ok()?;
+    let (minor, suffix) = match rest.split_once('-') {
+        Some((minor, suffix)) if !suffix.is_empty() => (minor, Some(suffix.to_string())),
+        Some(_) => return None,
+        None => (rest, None),
+    };
+    Some((major, minor.parse().ok()?, suffix))
+}
+
+// Suffixes marking a build that comes before the release it's named after
+const PRERELEASE_SUFFIXES: [&str; 4] = ["alpha", "beta", "pre", "rc"];
+
+// Split a suffix into its text and trailing number so RC10 sorts after RC9. Release candidates
+// and other prereleases sort before the plain release, and variants such as rtsp after it.
+fn suffix_key(suffix: Option<&str>) -> (u8, &str, Option<u64>) {
+    let Some(suffix) = suffix else {
+        return (1, "", None);
+    };
+    let text = suffix.trim_end_matches(|c: char| c.is_ascii_digit());
+    let rank = if PRERELEASE_SUFFIXES
+        .iter()
+        .any(|p| text.eq_ignore_ascii_case(p))
+    {
+        0
+    } else {
+        2
+    };
+    (rank, text, suffix[text.len()..].parse().ok())
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.install_type
+            .cmp(&other.install_type)
+            .then(self.major.cmp(&other.major))
+            .then(self.minor.cmp(&other.minor))
+            .then_with(|| {
+                suffix_key(self.suffix.as_deref()).cmp(&suffix_key(other.suffix.as_deref()))
+            })
+    }
+}
+
+// Equal when they sort the same, so RC03 and RC3 are the same release
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.tag())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::install_type::InstallType;
+    use crate::version::{bottles_directory_name, Version};
+
+    #[test]
+    fn can_parse_tags() {
+        let version = Version::parse(InstallType::Proton, "GE-Proton8-25").unwrap();
+        assert_eq!(
+            (version.major, version.minor, version.suffix),
+            (8, 25, None)
+        );
+
+        let version = Version::parse(InstallType::Proton, "GE-Proton9-1-rtsp").unwrap();
+        assert_eq!(version.suffix.as_deref(), Some("rtsp"));
+        assert_eq!(version.tag(), "GE-Proton9-1-rtsp");
+
+        let version = Version::parse(InstallType::ULWGL, "ULWGL-Proton-0.1-RC3").unwrap();
+        assert_eq!((version.major, version.minor), (0, 1));
+        assert_eq!(version.tag(), "ULWGL-Proton-0.1-RC3");
+
+        assert!(Version::parse(InstallType::Proton, "Proton-tkg").is_none());
+        assert!(Version::parse(InstallType::Proton, "GE-Proton8").is_none());
+    }
+
+    #[test]
+    fn can_map_directories_to_tags() {
+        let version =
+            Version::from_directory(InstallType::Wine, "lutris-GE-Proton8-26-x86_64").unwrap();
+        assert_eq!(version.tag(), "GE-Proton8-26");
+        assert_eq!(version.directory_name(), "lutris-GE-Proton8-26-x86_64");
+        assert!(Version::from_directory(InstallType::Wine, "GE-Proton8-26").is_none());
+
+        let bottles = bottles_directory_name("GE-Proton8-26");
+        assert_eq!(bottles, "wine-ge-proton8-26");
+        assert_eq!(
+            Version::from_directory(InstallType::Wine, &bottles).unwrap(),
+            version
+        );
+    }
+
+    #[test]
+    fn versions_sort_numerically() {
+        let mut versions: Vec<Version> = [
+            "GE-Proton8-4",
+            "GE-Proton9-1-rtsp",
+            "GE-Proton8-25",
+            "GE-Proton9-1",
+            "GE-Proton10-1",
+        ]
+        .iter()
+        .filter_map(|t| Version::parse(InstallType::Proton, t))
+        .collect();
+        versions.sort();
+        let tags: Vec<String> = versions.iter().map(Version::tag).collect();
+        assert_eq!(
+            tags,
+            [
+                "GE-Proton8-4",
+                "GE-Proton8-25",
+                "GE-Proton9-1",
+                "GE-Proton9-1-rtsp",
+                "GE-Proton10-1"
+            ]
+        );
+
+        let rc9 = Version::parse(InstallType::ULWGL, "ULWGL-Proton-0.1-RC9").unwrap();
+        let rc10 = Version::parse(InstallType::ULWGL, "ULWGL-Proton-0.1-RC10").unwrap();
+        let release = Version::parse(InstallType::ULWGL, "ULWGL-Proton-0.1").unwrap();
+        assert!(rc9 < rc10);
+        assert!(rc10 < release);
+        assert_eq!(
+            rc9,
+            Version::parse(InstallType::ULWGL, "ULWGL-Proton-0.1-RC09").unwrap()
+        );
+    }
+}
@@ -1,46 +1,41 @@
 use crate::constants::MAX_PER_PAGE;
+use crate::error::{Error, Result};
 use crate::github;
-use anyhow;
+use crate::install_type::InstallType;
+use crate::version::Version;
 use dirs::home_dir;
 
 pub async fn get_releases_paged(
-    url: String,
+    client: &github::api::Client,
+    repo: &str,
     mut number: u8,
     page: u8,
-) -> Option<github::api::Releases> {
+) -> Result<github::api::Releases> {
     if number > MAX_PER_PAGE {
         number = MAX_PER_PAGE
     }
 
-    let releases_wrapped = github::api::releases(&url, Some(number), Some(page));
-    let releases = match releases_wrapped.await {
-        Ok(e) => e,
-        Err(e) => {
-            println!("Error: {}", e);
-            return None;
-        }
-    };
-    Some(releases)
+    client.releases(repo, Some(number), Some(page)).await
 }
 
-pub fn get_installed_versions(path: &std::path::PathBuf) -> anyhow::Result<Vec<std::fs::DirEntry>> {
-    let mut home: std::path::PathBuf =
-        home_dir().ok_or(anyhow::anyhow!("Couldn't get users home directory"))?;
+pub fn get_installed_versions(path: &std::path::PathBuf) -> Result<Vec<std::fs::DirEntry>> {
+    let mut home: std::path::PathBuf = home_dir().ok_or(Error::HomeDirMissing)?;
     home.push(path);
-    let dir_entries_result = std::fs::read_dir(home);
+    let dir_entries_result = std::fs::read_dir(&home);
     let mut entries: Vec<std::fs::DirEntry> = Vec::new();
     let dir_entries = match dir_entries_result {
         Ok(d) => d,
         Err(_e) => {
-            return Err(anyhow::anyhow!(
-                "Failed to read compatibility directory. Does it exist?"
-            ));
+            return Err(Error::CompatDirMissing(home));
         }
     };
     for dir in dir_entries {
         match dir {
             Ok(d) => {
-                entries.push(d);
+                // Hidden entries such as the staging directory aren't installs
+                if !d.file_name().to_string_lossy().starts_with('.') {
+                    entries.push(d);
+                }
             }
             Err(_) => {
                 break;
@@ -50,19 +45,45 @@ pub fn get_installed_versions(path: &std::path::PathBuf) -> anyhow::Result<Vec<s
     Ok(entries)
 }
 
+// Installed releases of install_type, newest first. Entries that don't parse are skipped.
+pub fn get_installed_releases(
+    install_type: InstallType,
+    path: &std::path::PathBuf,
+) -> Result<Vec<(Version, std::fs::DirEntry)>> {
+    let mut releases: Vec<(Version, std::fs::DirEntry)> = get_installed_versions(path)?
+        .into_iter()
+        .filter_map(|e| {
+            let version = Version::from_directory(install_type, e.file_name().to_str()?)?;
+            Some((version, e))
+        })
+        .collect();
+    releases.sort_by(|a, b| b.0.cmp(&a.0));
+    Ok(releases)
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
-    fn can_get_local_dir() -> anyhow::Result<()> {
+    fn can_get_local_dir() -> std::io::Result<()> {
         use crate::install_type::InstallType;
-        use crate::version_info::get_installed_versions;
+        use crate::test_utils::TempDir;
+        use crate::version_info::{get_installed_releases, get_installed_versions};
 
-        let install = InstallType::Proton;
-        let results = get_installed_versions(&install.get_compat_directory_safe().unwrap())?;
-        if !results.is_empty() {
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("Directory was empty"))
+        let dir = TempDir::new("local-dir");
+        for name in [
+            "GE-Proton8-4",
+            "GE-Proton8-25",
+            "custom",
+            ".protonctl-staging",
+        ] {
+            std::fs::create_dir_all(dir.join(name))?;
         }
+
+        let results = get_installed_versions(&dir.path).unwrap();
+        assert_eq!(results.len(), 3);
+        let releases = get_installed_releases(InstallType::Proton, &dir.path).unwrap();
+        let names: Vec<_> = releases.iter().map(|(v, _)| v.tag()).collect();
+        assert_eq!(names, ["GE-Proton8-25", "GE-Proton8-4"]);
+        Ok(())
     }
 }
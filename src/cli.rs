@@ -1,40 +1,4 @@
-use clap::{builder::PossibleValue, value_parser, Arg, ArgAction, Command, ValueEnum};
-
-#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Debug)]
-pub enum InstallTypeCmd {
-    Proton,
-    Wine,
-    ULWGL,
-}
-
-impl Default for InstallTypeCmd {
-    fn default() -> Self {
-        Self::Proton
-    }
-}
-
-impl ValueEnum for InstallTypeCmd {
-    fn value_variants<'a>() -> &'a [Self] {
-        &[Self::Proton, Self::Wine, Self::ULWGL]
-    }
-
-    fn to_possible_value(&self) -> Option<PossibleValue> {
-        match self {
-            InstallTypeCmd::Wine => Some(PossibleValue::new("wine")),
-            InstallTypeCmd::Proton => Some(PossibleValue::new("proton")),
-            InstallTypeCmd::ULWGL => Some(PossibleValue::new("ulwgl")),
-        }
-    }
-
-    fn from_str(input: &str, _ignore_case: bool) -> Result<Self, String> {
-        match input {
-            "wine" => Ok(Self::Wine),
-            "proton" => Ok(Self::Proton),
-            "ulwgl" => Ok(Self::ULWGL),
-            _ => Err(format!("Invalid argument: {}", input)),
-        }
-    }
-}
+use clap::{value_parser, Arg, ArgAction, Command};
 
 pub fn build_cli() -> Command {
     Command::new("protonctl")
@@ -45,11 +9,19 @@ pub fn build_cli() -> Command {
                 .short('t')
                 .long("type")
                 .action(ArgAction::Set)
-                .value_parser(clap::builder::EnumValueParser::<InstallTypeCmd>::new())
-                .default_value("proton")
+                .value_parser(value_parser!(String))
                 .global(true)
                 .required(false)
-                .help("The type install type to use"),
+                .help("The install type to use: proton, wine, ulwgl or a source from the config file. Defaults to defaults.type from the config"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .action(ArgAction::Set)
+                .value_parser(value_parser!(std::path::PathBuf))
+                .global(true)
+                .required(false)
+                .help("The config file to use instead of PROTONCTL_CONFIG or ~/.config/protonctl/config.toml"),
         )
         .arg(
             Arg::new("flatpak")
@@ -58,7 +30,33 @@ pub fn build_cli() -> Command {
                 .action(ArgAction::SetTrue)
                 .required(false)
                 .default_value("false")
-                .help("Use flatpak"),
+                .conflicts_with("target")
+                .help("Use flatpak. Short for --target flatpak"),
+        )
+        .arg(
+            Arg::new("dry_run")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("Print what would be downloaded, written and deleted without doing it"),
+        )
+        .arg(
+            Arg::new("target")
+                .long("target")
+                .action(ArgAction::Append)
+                .value_parser([
+                    "native",
+                    "flatpak",
+                    "snap",
+                    "heroic",
+                    "heroic-flatpak",
+                    "bottles",
+                    "bottles-flatpak",
+                    "all",
+                ])
+                .global(true)
+                .required(false)
+                .help("Which installs of Steam, Lutris, Heroic or Bottles to use. Can be repeated. Defaults to the only one found"),
         )
         .subcommand(
             Command::new("list")
@@ -66,11 +64,10 @@ pub fn build_cli() -> Command {
                     Arg::new("number")
                         .action(ArgAction::Set)
                         .value_parser(value_parser!(u8))
-                        .default_value("10")
                         .required(false)
                         .short('n')
                         .long("number")
-                        .help("The number of releases to list"),
+                        .help("The number of releases to list. Defaults to defaults.number from the config"),
                 )
                 .arg(
                     Arg::new("page")
@@ -113,7 +110,7 @@ pub fn build_cli() -> Command {
                         .short('a')
                         .long("all")
                         .conflicts_with("cache")
-                        .help("Delete all proton or wine installs"),
+                        .help("Delete all proton or wine installs made by protonctl"),
                 )
                 .arg(
                     Arg::new("install_version")
@@ -121,11 +118,68 @@ pub fn build_cli() -> Command {
                         .value_parser(value_parser!(String))
                         .required_unless_present_any(["cache", "all"])
                         .help("Install selected version"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .action(ArgAction::SetTrue)
+                        .default_value("false")
+                        .required(false)
+                        .long("force")
+                        .conflicts_with("cache")
+                        .help("Also delete installs that weren't made by protonctl or that games still use"),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .action(ArgAction::SetTrue)
+                        .default_value("false")
+                        .required(false)
+                        .short('y')
+                        .long("yes")
+                        .help("Don't ask before deleting everything with --all"),
+                ),
+        )
+        .subcommand(
+            Command::new("prune")
+                .about("Delete all but the newest builds installed by protonctl")
+                .arg(
+                    Arg::new("keep")
+                        .long("keep")
+                        .action(ArgAction::Set)
+                        .value_parser(value_parser!(usize))
+                        .required(true)
+                        .help("How many of the newest builds to keep in each target"),
+                )
+                .arg(
+                    Arg::new("pin")
+                        .long("pin")
+                        .action(ArgAction::Append)
+                        .help("A build to keep however old it is, by directory name or tag. Can be repeated"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .action(ArgAction::SetTrue)
+                        .default_value("false")
+                        .required(false)
+                        .long("force")
+                        .help("Also delete builds that games still use"),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .action(ArgAction::SetTrue)
+                        .default_value("false")
+                        .required(false)
+                        .short('y')
+                        .long("yes")
+                        .help("Don't ask before deleting"),
                 ),
         )
         .subcommand(
             Command::new("install")
-                .arg(Arg::new("install_version").required(true))
+                .arg(
+                    Arg::new("install_version")
+                        .required(true)
+                        .help("The release tag to install, or latest / latest~N for the newest or Nth newest release"),
+                )
                 .arg(
                     Arg::new("skip_sha_check")
                         .action(ArgAction::SetTrue)
@@ -133,6 +187,160 @@ pub fn build_cli() -> Command {
                         .required(false)
                         .long("skip-sha-check")
                         .help("Don't attempt to fetch or validate the sha"),
+                )
+                .arg(
+                    Arg::new("no_resume")
+                        .action(ArgAction::SetTrue)
+                        .default_value("false")
+                        .required(false)
+                        .long("no-resume")
+                        .help("Download from the start and delete partial downloads on failure"),
+                )
+                .arg(
+                    Arg::new("set_default")
+                        .action(ArgAction::SetTrue)
+                        .default_value("false")
+                        .required(false)
+                        .long("set-default")
+                        .help("Make the release Steam's default compatibility tool once installed"),
+                ),
+        )
+        .subcommand(
+            Command::new("update")
+                .arg(
+                    Arg::new("remove_old")
+                        .action(ArgAction::SetTrue)
+                        .default_value("false")
+                        .required(false)
+                        .long("remove-old")
+                        .help("Delete the installs superseded by the newest release"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .action(ArgAction::SetTrue)
+                        .default_value("false")
+                        .required(false)
+                        .long("force")
+                        .requires("remove_old")
+                        .help("Also delete superseded installs that weren't made by protonctl or that games still use"),
+                )
+                .arg(
+                    Arg::new("skip_sha_check")
+                        .action(ArgAction::SetTrue)
+                        .default_value("false")
+                        .required(false)
+                        .long("skip-sha-check")
+                        .help("Don't attempt to fetch or validate the sha"),
+                )
+                .arg(
+                    Arg::new("no_resume")
+                        .action(ArgAction::SetTrue)
+                        .default_value("false")
+                        .required(false)
+                        .long("no-resume")
+                        .help("Download from the start and delete partial downloads on failure"),
                 ),
         )
+        .subcommand(
+            Command::new("config")
+                .about("Show or edit the config file")
+                .subcommand_required(true)
+                .subcommand(Command::new("show").about("Print the config with defaults filled in"))
+                .subcommand(
+                    Command::new("get")
+                        .about("Print the value of a key such as defaults.number")
+                        .arg(Arg::new("key").required(true)),
+                )
+                .subcommand(
+                    Command::new("set")
+                        .about("Set a key in the config file")
+                        .arg(Arg::new("key").required(true))
+                        .arg(Arg::new("value").required(true)),
+                )
+                .subcommand(Command::new("path").about("Print the path of the config file")),
+        )
+        .subcommand(
+            Command::new("steam")
+                .about("Choose the compatibility tools Steam uses for games")
+                .subcommand_required(true)
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .action(ArgAction::SetTrue)
+                        .default_value("false")
+                        .global(true)
+                        .help("Edit Steam's config even while Steam is running"),
+                )
+                .subcommand(
+                    Command::new("set-tool")
+                        .about("Set the compatibility tool for a game")
+                        .arg(
+                            Arg::new("app")
+                                .required(true)
+                                .help("The app id or the name of an installed game"),
+                        )
+                        .arg(
+                            Arg::new("tool")
+                                .required(true)
+                                .help("The compatibility tool, e.g. GE-Proton8-25"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("unset-tool")
+                        .about("Go back to Steam's default tool for a game")
+                        .arg(
+                            Arg::new("app")
+                                .required(true)
+                                .help("The app id or the name of an installed game"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("default")
+                        .about("Set the Steam Play default compatibility tool for every game")
+                        .arg(
+                            Arg::new("tool")
+                                .required(true)
+                                .help("An installed compatibility tool, e.g. GE-Proton8-25"),
+                        ),
+                )
+                .subcommand(Command::new("show").about("List the compatibility tool of each game")),
+        )
+        .subcommand(
+            Command::new("lutris")
+                .about("Choose the wine versions Lutris uses for games")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("set-version")
+                        .about("Set the wine version for a game")
+                        .arg(
+                            Arg::new("game")
+                                .required(true)
+                                .help("The slug, id or name of a Lutris game"),
+                        )
+                        .arg(
+                            Arg::new("version")
+                                .required(true)
+                                .help("The wine runner directory or release tag, e.g. GE-Proton8-26"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("unset-version")
+                        .about("Go back to the default wine version for a game")
+                        .arg(
+                            Arg::new("game")
+                                .required(true)
+                                .help("The slug, id or name of a Lutris game"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("default")
+                        .about("Set the default wine version in runners/wine.yml")
+                        .arg(
+                            Arg::new("version")
+                                .required(true)
+                                .help("The wine runner directory or release tag, e.g. GE-Proton8-26"),
+                        ),
+                )
+                .subcommand(Command::new("show").about("List the wine version of each game")),
+        )
 }
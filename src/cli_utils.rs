@@ -1,129 +1,107 @@
-use crate::{cli::InstallTypeCmd, install, list, remove};
+use crate::{
+    config, config::ConfigAction, install, list, lutris, lutris::LutrisAction, prune, remove,
+    steam, steam::SteamAction, update,
+};
+use anyhow::Context;
 use async_trait::async_trait;
 use clap::Command;
-use dirs::home_dir;
-use protonctllib::constants;
-use std::fmt::Display;
+use console::{Style, Term};
+use indicatif::HumanBytes;
+use protonctllib::{
+    config::{Config, CONFIG_ENV},
+    install_type::InstallType,
+    source::Source,
+    target::{self, Target},
+};
+use std::process::ExitCode;
 
 #[async_trait]
 pub trait Run {
     async fn run(&self) -> anyhow::Result<()>;
 }
 
-impl Display for InstallTypeCmd {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            InstallTypeCmd::Wine => write!(f, "wine"),
-            InstallTypeCmd::Proton => write!(f, "proton"),
-            InstallTypeCmd::ULWGL => write!(f, "ulwgl"),
-        }
-    }
-}
-
-impl InstallTypeCmd {
-    pub fn get_url(&self, latest: bool) -> String {
-        match self {
-            InstallTypeCmd::Wine => {
-                if latest {
-                    format!(
-                        "https://api.github.com/repos/{}/{}/releases/latest",
-                        constants::GE_PROJECT_OWNER,
-                        constants::WINE_PROJECT_NAME
-                    )
-                } else {
-                    format!(
-                        "https://api.github.com/repos/{}/{}/releases",
-                        constants::GE_PROJECT_OWNER,
-                        constants::WINE_PROJECT_NAME
-                    )
-                }
-            }
-            InstallTypeCmd::Proton => {
-                if latest {
-                    format!(
-                        "https://api.github.com/repos/{}/{}/releases/latest",
-                        constants::GE_PROJECT_OWNER,
-                        constants::PROTON_PROJECT_NAME
-                    )
-                } else {
-                    format!(
-                        "https://api.github.com/repos/{}/{}/releases",
-                        constants::GE_PROJECT_OWNER,
-                        constants::PROTON_PROJECT_NAME
-                    )
-                }
-            }
-            InstallTypeCmd::ULWGL => {
-                if latest {
-                    format!(
-                        "https://api.github.com/repos/{}/{}/releases/latest",
-                        constants::ULWGL_PROJECT_OWNER,
-                        constants::ULWGL_PROJECT_NAME
-                    )
-                } else {
-                    format!(
-                        "https://api.github.com/repos/{}/{}/releases",
-                        constants::ULWGL_PROJECT_OWNER,
-                        constants::ULWGL_PROJECT_NAME
-                    )
-                }
-            }
-        }
-    }
-
-    pub fn get_compat_directory_safe(
-        &self,
-        is_flatpak: bool,
-    ) -> anyhow::Result<std::path::PathBuf> {
-        let mut compat_dir =
-            home_dir().ok_or(anyhow::anyhow!("Failed to get users home directory"))?;
-        let compat_path = match self {
-            InstallTypeCmd::Wine => {
-                if is_flatpak {
-                    std::path::PathBuf::from(".var/app/net.lutris.Lutris/data/lutris/runners/wine")
-                } else {
-                    std::path::PathBuf::from(".local/share/lutris/runners/wine")
-                }
-            }
-            InstallTypeCmd::Proton => {
-                if is_flatpak {
-                    std::path::PathBuf::from(
-                        ".var/app/com.valvesoftware.Steam/.local/share/Steam/compatibilitytools.d",
-                    )
-                } else {
-                    std::path::PathBuf::from(".local/share/Steam/compatibilitytools.d")
-                }
+pub fn command_to_struct(cmd: &Command) -> anyhow::Result<Box<dyn Run>> {
+    let matches = cmd.clone().get_matches();
+    let config_path = match matches.get_one::<std::path::PathBuf>("config") {
+        Some(path) => path.clone(),
+        None => Config::path()?,
+    };
+    if let Some(("config", sub_c)) = matches.subcommand() {
+        // Config commands have to work on files that are missing or fail to validate
+        let action = match sub_c.subcommand() {
+            Some(("show", _)) => ConfigAction::Show,
+            Some(("get", sub_g)) => {
+                ConfigAction::Get(sub_g.get_one::<String>("key").unwrap().clone())
             }
-            InstallTypeCmd::ULWGL => std::path::PathBuf::from(".local/share/ULWGL-Proton/"),
+            Some(("set", sub_s)) => ConfigAction::Set(
+                sub_s.get_one::<String>("key").unwrap().clone(),
+                sub_s.get_one::<String>("value").unwrap().clone(),
+            ),
+            _ => ConfigAction::Path,
         };
-        compat_dir.push(compat_path);
-        if !compat_dir.exists() {
-            std::fs::create_dir_all(&compat_dir)?;
-            Ok(compat_dir)
-        } else {
-            Ok(compat_dir)
-        }
+        return Ok(Box::new(config::ConfigCmd::new(
+            config_path,
+            action,
+            *matches.get_one::<bool>("dry_run").unwrap(),
+        )));
     }
-}
+    // A missing file only falls back to the defaults when it wasn't asked for explicitly
+    let explicit = matches.contains_id("config") || std::env::var_os(CONFIG_ENV).is_some();
+    if explicit && !config_path.exists() {
+        return Err(protonctllib::Error::Config(format!(
+            "Config file {:?} doesn't exist",
+            config_path
+        ))
+        .into());
+    }
+    let config = Config::load_from(&config_path).context("Failed to load config")?;
+    let dry_run = *matches.get_one::<bool>("dry_run").unwrap();
 
-pub fn command_to_struct(cmd: &Command) -> anyhow::Result<Box<dyn Run>> {
-    let matches = cmd.clone().get_matches();
-    let flatpak = *matches.get_one::<bool>("flatpak").unwrap();
-    let install_type = *matches.get_one::<InstallTypeCmd>("type").unwrap();
+    // -f is short for --target flatpak
+    let mut requested: Vec<&str> = matches
+        .get_many::<String>("target")
+        .unwrap_or_default()
+        .map(String::as_str)
+        .collect();
+    if *matches.get_one::<bool>("flatpak").unwrap() {
+        requested.push("flatpak");
+    }
+    let source = Source::resolve(
+        matches
+            .get_one::<String>("type")
+            .unwrap_or(&config.defaults.install_type),
+        &config,
+    )?;
     match matches.subcommand() {
         Some(("install", sub_i)) => Ok(Box::new(install::Install::new(
             sub_i.get_one::<String>("install_version").unwrap().clone(),
-            flatpak,
+            target::select(&source, &requested)?,
             *sub_i.get_one::<bool>("skip_sha_check").unwrap(),
-            install_type,
-        ))),
-        Some(("list", sub_l)) => Ok(Box::new(list::List::new(
-            *sub_l.get_one::<u8>("number").unwrap(),
-            *sub_l.get_one::<u8>("page").unwrap(),
-            *sub_l.get_one::<bool>("local").unwrap(),
-            flatpak,
-            install_type,
+            source,
+            !*sub_i.get_one::<bool>("no_resume").unwrap(),
+            *sub_i.get_one::<bool>("set_default").unwrap(),
+            dry_run,
+            config,
         ))),
+        Some(("list", sub_l)) => {
+            let local = *sub_l.get_one::<bool>("local").unwrap();
+            Ok(Box::new(list::List::new(
+                sub_l
+                    .get_one::<u8>("number")
+                    .copied()
+                    .unwrap_or(config.defaults.number),
+                *sub_l.get_one::<u8>("page").unwrap(),
+                local,
+                // Remote listings don't go to a target
+                if local {
+                    target::select(&source, &requested)?
+                } else {
+                    Vec::new()
+                },
+                source,
+                config,
+            )))
+        }
         Some(("remove", sub_r)) => {
             let cache = *sub_r.get_one::<bool>("cache").unwrap();
             let all = *sub_r.get_one::<bool>("all").unwrap();
@@ -139,11 +117,191 @@ pub fn command_to_struct(cmd: &Command) -> anyhow::Result<Box<dyn Run>> {
             Ok(Box::new(remove::Remove::new(
                 cache,
                 all,
-                flatpak,
-                install_type,
+                if cache {
+                    Vec::new()
+                } else {
+                    target::select(&source, &requested)?
+                },
+                source,
                 install_version,
+                *sub_r.get_one::<bool>("force").unwrap(),
+                *sub_r.get_one::<bool>("yes").unwrap(),
+                dry_run,
+                config,
+            )))
+        }
+        Some(("update", sub_u)) => Ok(Box::new(update::Update::new(
+            target::select(&source, &requested)?,
+            *sub_u.get_one::<bool>("skip_sha_check").unwrap(),
+            *sub_u.get_one::<bool>("remove_old").unwrap(),
+            *sub_u.get_one::<bool>("force").unwrap(),
+            source,
+            !*sub_u.get_one::<bool>("no_resume").unwrap(),
+            dry_run,
+            config,
+        ))),
+        Some(("prune", sub_p)) => Ok(Box::new(prune::Prune::new(
+            *sub_p.get_one::<usize>("keep").unwrap(),
+            sub_p
+                .get_many::<String>("pin")
+                .map(|pins| pins.cloned().collect())
+                .unwrap_or_default(),
+            *sub_p.get_one::<bool>("force").unwrap(),
+            *sub_p.get_one::<bool>("yes").unwrap(),
+            dry_run,
+            target::select(&source, &requested)?,
+            source,
+            config,
+        ))),
+        Some(("steam", sub_s)) => {
+            // Steam only has the one config, so a single target has to be picked
+            let target = target::select_one(
+                &InstallType::Proton.into(),
+                &requested,
+                "Steam",
+                &protonctllib::steam::TARGETS,
+            )?;
+            let action = match sub_s.subcommand() {
+                Some(("set-tool", sub_t)) => SteamAction::SetTool(
+                    sub_t.get_one::<String>("app").unwrap().clone(),
+                    sub_t.get_one::<String>("tool").unwrap().clone(),
+                ),
+                Some(("unset-tool", sub_t)) => {
+                    SteamAction::UnsetTool(sub_t.get_one::<String>("app").unwrap().clone())
+                }
+                Some(("default", sub_d)) => {
+                    SteamAction::Default(sub_d.get_one::<String>("tool").unwrap().clone())
+                }
+                _ => SteamAction::Show,
+            };
+            Ok(Box::new(steam::SteamCmd::new(
+                action,
+                target,
+                *sub_s.get_one::<bool>("force").unwrap(),
+                dry_run,
+                config,
+            )))
+        }
+        Some(("lutris", sub_l)) => {
+            let target = target::select_one(
+                &InstallType::Wine.into(),
+                &requested,
+                "Lutris",
+                &protonctllib::lutris::TARGETS,
+            )?;
+            let action = match sub_l.subcommand() {
+                Some(("set-version", sub_v)) => LutrisAction::SetVersion(
+                    sub_v.get_one::<String>("game").unwrap().clone(),
+                    sub_v.get_one::<String>("version").unwrap().clone(),
+                ),
+                Some(("unset-version", sub_v)) => {
+                    LutrisAction::UnsetVersion(sub_v.get_one::<String>("game").unwrap().clone())
+                }
+                Some(("default", sub_d)) => {
+                    LutrisAction::Default(sub_d.get_one::<String>("version").unwrap().clone())
+                }
+                _ => LutrisAction::Show,
+            };
+            Ok(Box::new(lutris::LutrisCmd::new(
+                action, target, dry_run, config,
             )))
         }
         _ => Err(anyhow::anyhow!("It shouldn't be possible to hit this")),
     }
 }
+
+// Print builds about to be deleted as a table of name, target and size with the total
+pub(crate) fn print_builds(config: &Config, builds: &[(&str, Target, u64)]) {
+    let term = Term::stdout();
+    let prefix_style = Style::from_dotted_str(&config.styles.prefix);
+    let directory_style = Style::from_dotted_str(&config.styles.directory);
+    let header_style = Style::from_dotted_str(&config.styles.header);
+    let name_width = builds.iter().map(|b| b.0.len()).max().unwrap_or(0);
+    let target_width = builds
+        .iter()
+        .map(|b| b.1.to_string().len())
+        .max()
+        .unwrap_or(0);
+    term.write_line(&format!("{}", header_style.apply_to("Builds to remove:")))
+        .unwrap();
+    for (name, target, size) in builds {
+        term.write_line(&format!(
+            "  {} {:<target_width$} {:>11}",
+            directory_style.apply_to(format!("{:<name_width$}", name)),
+            target.to_string(),
+            HumanBytes(*size).to_string()
+        ))
+        .unwrap();
+    }
+    let total: u64 = builds.iter().map(|b| b.2).sum();
+    term.write_line(&format!(
+        "{} {}",
+        prefix_style.apply_to("Total:"),
+        HumanBytes(total)
+    ))
+    .unwrap();
+}
+
+// Print a step that --dry-run skips, e.g. "Would delete <path> (1.20 GiB)"
+pub(crate) fn would(
+    config: &Config,
+    action: &str,
+    what: impl std::fmt::Display,
+    size: Option<u64>,
+) {
+    let prefix_style = Style::from_dotted_str(&config.styles.prefix);
+    let size = size
+        .map(|size| format!(" ({})", HumanBytes(size)))
+        .unwrap_or_default();
+    Term::stdout()
+        .write_line(&format!(
+            "{} {}{}",
+            prefix_style.apply_to(format!("Would {}", action)),
+            what,
+            size
+        ))
+        .unwrap();
+}
+
+// Ask on stderr and read the answer from stdin. Anything but y or yes, including no input at
+// all, is a no.
+pub(crate) fn confirm(question: &str) -> anyhow::Result<bool> {
+    eprint!("{} [y/N] ", question);
+    let mut answer = String::new();
+    if std::io::stdin()
+        .read_line(&mut answer)
+        .context("Failed to read the answer")?
+        == 0
+    {
+        eprintln!();
+    }
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+// Map the first protonctl error in the chain onto an exit code. 2 is shared with clap's usage
+// errors.
+pub fn exit_code(error: &anyhow::Error) -> ExitCode {
+    use protonctllib::Error;
+    let code = error.chain().find_map(|e| {
+        e.downcast_ref::<Error>().map(|e| match e {
+            Error::Network(_) | Error::DownloadInterrupted(_) => 3,
+            Error::NotFound(_) => 4,
+            Error::RateLimited { .. } => 5,
+            Error::Api { .. } | Error::DownloadStatus(_) => 6,
+            Error::HashMismatch { .. } | Error::InvalidChecksum(_) | Error::SizeMismatch { .. } => {
+                7
+            }
+            Error::UnknownArchive(_) | Error::Extraction { .. } | Error::InvalidRelease(_) => 8,
+            Error::HomeDirMissing
+            | Error::CompatDirMissing(_)
+            | Error::Io { .. }
+            | Error::Manifest { .. }
+            | Error::Vdf(_)
+            | Error::Yaml { .. }
+            | Error::Database { .. } => 9,
+            Error::Config(_) => 10,
+            Error::AmbiguousTarget(_) => 2,
+        })
+    });
+    ExitCode::from(code.unwrap_or(1))
+}
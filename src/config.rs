@@ -0,0 +1,58 @@
+use crate::cli_utils::{would, Run};
+use anyhow::Context;
+use async_trait::async_trait;
+use protonctllib::config::Config;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum ConfigAction {
+    Show,
+    Get(String),
+    Set(String, String),
+    Path,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct ConfigCmd {
+    pub path: std::path::PathBuf,
+    pub action: ConfigAction,
+    pub dry_run: bool,
+}
+
+impl ConfigCmd {
+    pub fn new(path: std::path::PathBuf, action: ConfigAction, dry_run: bool) -> Self {
+        Self {
+            path,
+            action,
+            dry_run,
+        }
+    }
+}
+
+#[async_trait]
+impl Run for ConfigCmd {
+    async fn run(&self) -> anyhow::Result<()> {
+        match &self.action {
+            ConfigAction::Path => println!("{}", self.path.display()),
+            ConfigAction::Show => {
+                let config = Config::load_from(&self.path).context("Failed to load config")?;
+                print!("{}", config.show()?);
+            }
+            ConfigAction::Get(key) => {
+                let config = Config::load_from(&self.path).context("Failed to load config")?;
+                match config.get(key)? {
+                    Some(value) => println!("{}", value),
+                    None => eprintln!("{} is not set", key),
+                }
+            }
+            ConfigAction::Set(key, value) if self.dry_run => {
+                Config::with_value(&self.path, key, value)?;
+                // The file being edited may not load, so its styles can't be used
+                would(&Config::default(), "write", self.path.display(), None);
+            }
+            ConfigAction::Set(key, value) => {
+                Config::set(&self.path, key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
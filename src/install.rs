@@ -1,46 +1,65 @@
-use crate::cli::InstallTypeCmd;
-use crate::cli_utils::Run;
+use crate::cli_utils::{would, Run};
+use crate::steam;
 use anyhow::Context;
 use async_trait::async_trait;
 use console::{Style, Term};
 use futures_util::StreamExt;
 use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 use protonctllib::{
+    config::{self, Config},
+    constants::MAX_PER_PAGE,
     decompress,
-    github::api::{
-        download_asset, download_asset_to_memory, get_asset_id, release_version, AssetType, Release,
-    },
-    utils,
+    github::api::{AssetId, Client, Release},
+    install_type::InstallType,
+    manifest::{Manifest, ManifestEntry},
+    source::Source,
+    target::Target,
+    utils, Error,
 };
-use reqwest::Response;
+use reqwest::StatusCode;
 use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Default)]
+#[derive(Debug, Eq, PartialEq, Default)]
 pub struct Install {
     pub install_version: String,
-    pub flatpak: bool,
+    pub targets: Vec<Target>,
     pub skip_sha_check: bool,
-    pub install_type: InstallTypeCmd,
+    pub source: Source,
+    pub resume: bool,
+    pub set_default: bool,
+    pub dry_run: bool,
+    pub config: Config,
 }
 
 impl Install {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         install_version: String,
-        flatpak: bool,
+        targets: Vec<Target>,
         skip_sha_check: bool,
-        install_type: InstallTypeCmd,
+        source: Source,
+        resume: bool,
+        set_default: bool,
+        dry_run: bool,
+        config: Config,
     ) -> Self {
         Self {
             install_version,
-            flatpak,
+            targets,
             skip_sha_check,
-            install_type,
+            source,
+            resume,
+            set_default,
+            dry_run,
+            config,
         }
     }
 }
 
-// Struct containing all the styles we use in the run function.
-// It may be worth allowing the user to customize in the future.
+// Struct containing all the styles we use in the run function, set in the styles section of
+// the config
 pub(crate) struct Styles {
     success_style: Style,
     fail_style: Style,
@@ -48,11 +67,11 @@ pub(crate) struct Styles {
 }
 
 impl Styles {
-    pub fn new() -> Self {
+    pub fn new(styles: &config::Styles) -> Self {
         Self {
-            success_style: Style::new().italic().bold().green(),
-            fail_style: Style::new().italic().bold().red(),
-            prefix_style: Style::new().white().bold(),
+            success_style: Style::from_dotted_str(&styles.success),
+            fail_style: Style::from_dotted_str(&styles.fail),
+            prefix_style: Style::from_dotted_str(&styles.prefix),
         }
     }
 }
@@ -61,36 +80,172 @@ impl Styles {
 impl Run for Install {
     async fn run(&self) -> anyhow::Result<()> {
         // Get terminal and styles setup
-        let mut term = Term::stderr();
-        let styles = Styles::new();
-        // Get information we need to start the download ( install path, download path, assetids )
-        let compat_directory: std::path::PathBuf = self
-            .install_type
-            .get_compat_directory_safe(self.flatpak)
+        let term = Term::stderr();
+        let styles = Styles::new(&self.config.styles);
+        if self.set_default {
+            if self.source.install_type() != Some(InstallType::Proton) {
+                return Err(protonctllib::Error::Config(format!(
+                    "--set-default only works for proton, not {}",
+                    self.source
+                ))
+                .into());
+            }
+            // Only Steam has a default tool, so the other targets just get the install
+            let others: Vec<String> = self
+                .targets
+                .iter()
+                .filter(|t| !protonctllib::steam::TARGETS.contains(t))
+                .map(|t| t.to_string())
+                .collect();
+            if others.len() == self.targets.len() {
+                return Err(protonctllib::Error::Config(format!(
+                    "--set-default needs a Steam target, not {}",
+                    others.join(", ")
+                ))
+                .into());
+            }
+            if !others.is_empty() {
+                eprintln!(
+                    "Not setting the Steam Play default for {}: it's not Steam",
+                    others.join(", ")
+                );
+            }
+            // Better to stop before downloading than after
+            if !self.dry_run {
+                steam::check_running(false)?;
+            }
+        }
+        let client = self.config.client()?;
+        let repo = self.source.repository();
+        let latest = parse_latest(&self.install_version)?;
+        let release: Release = match latest {
+            Some(back) => {
+                let release = self.resolve_latest(&client, &repo, back).await?;
+                term.write_line(
+                    format!(
+                        "{} {}",
+                        styles
+                            .prefix_style
+                            .apply_to(format!("Resolved {} to", self.install_version)),
+                        styles.success_style.apply_to(&release.tag_name)
+                    )
+                    .as_str(),
+                )
+                .unwrap();
+                release
+            }
+            None => {
+                // Accept installed directory names as well as tags
+                let tag = self
+                    .source
+                    .version_of_directory(&self.install_version)
+                    .map(|v| v.tag())
+                    .unwrap_or(self.install_version.clone());
+                client.release_version(&repo, &tag).await?
+            }
+        };
+        let mut destinations = Vec::new();
+        for target in &self.targets {
+            let compat_directory = if self.dry_run {
+                self.source.compat_directory(&self.config, *target)
+            } else {
+                self.source.get_compat_directory_safe(&self.config, *target)
+            }
             .context("Failed to get compatibility directory")?;
-        let url = self.install_type.get_url(false);
-        let release: Release = release_version(&url, &self.install_version).await?;
-        let mut install_path = utils::get_download_directory_safe()?;
+            // Only latest skips installed releases, an explicit tag is reinstalled
+            if latest.is_some()
+                && self.is_installed(&release.tag_name, *target, &compat_directory)?
+            {
+                term.write_line(
+                    format!(
+                        "{}",
+                        styles.prefix_style.apply_to(format!(
+                            "{} is already installed ({})",
+                            release.tag_name, target
+                        ))
+                    )
+                    .as_str(),
+                )
+                .unwrap();
+                continue;
+            }
+            destinations.push((*target, compat_directory));
+        }
+        if !destinations.is_empty() {
+            self.install_release(&client, &repo, &release, &destinations)
+                .await?;
+        }
+        if self.set_default {
+            for target in self
+                .targets
+                .iter()
+                .filter(|t| protonctllib::steam::TARGETS.contains(t))
+            {
+                if self.dry_run {
+                    would(
+                        &self.config,
+                        &format!("make {} the Steam Play default in", release.tag_name),
+                        protonctllib::steam::root_for(*target)?
+                            .join("config/config.vdf")
+                            .display(),
+                        None,
+                    );
+                    continue;
+                }
+                steam::set_default_tool(&self.config, *target, &release.tag_name, false)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Install {
+    // Download and verify a release once and install it into the compatibility directory of
+    // every target
+    pub(crate) async fn install_release(
+        &self,
+        client: &Client,
+        repo: &str,
+        release: &Release,
+        destinations: &[(Target, PathBuf)],
+    ) -> anyhow::Result<()> {
+        if self.dry_run {
+            return self.plan_release(client, repo, release, destinations);
+        }
+        let mut term = Term::stderr();
+        let styles = Styles::new(&self.config.styles);
+        let download_directory = self.config.download_directory()?;
+        let mut install_path = download_directory.clone();
 
-        let tar_asset = get_asset_id(&release, AssetType::Tar);
+        let tar_asset = self.tarball_asset(release)?;
         install_path.push(&tar_asset.name);
 
-        let tar_path = handle_install(
-            &install_path,
-            download_asset(url.clone(), &tar_asset).await?,
-        )
-        .await?;
+        let tar_path = handle_install(&install_path, client, repo, &tar_asset, self.resume).await?;
+        let tar_hash = utils::sha512(&tar_path)?;
 
-        // The contents of this if statement should be extracted into a separate function
-        if !self.skip_sha_check {
-            let sha_asset = get_asset_id(&release, AssetType::Sha);
-            let sha_string = download_asset_to_memory(url, &sha_asset).await?;
+        let sha_asset = self.source.checksum_asset(release);
+        if !self.skip_sha_check && sha_asset.is_none() {
+            term.write_line(
+                format!(
+                    "{}",
+                    styles.prefix_style.apply_to(format!(
+                        "{} has no checksum. Skipping hash check",
+                        release.tag_name
+                    ))
+                )
+                .as_str(),
+            )
+            .unwrap();
+        }
+        if let Some(sha_asset) = sha_asset.filter(|_| !self.skip_sha_check) {
+            let sha_string = client.download_asset_to_memory(repo, &sha_asset).await?;
+            let sha_string = utils::checksum_for(&sha_string, &tar_asset.name);
             term.write_fmt(format_args!(
                 "{}",
                 styles.prefix_style.apply_to("Checking hash ... ")
             ))
             .unwrap();
-            match utils::check_sha(&tar_path, &sha_string) {
+            match utils::compare_sha(&tar_hash, sha_string) {
                 Ok(is_match) => {
                     if is_match {
                         term.write_fmt(format_args!(
@@ -101,73 +256,337 @@ impl Run for Install {
                     } else {
                         term.write_fmt(format_args!("{}", styles.fail_style.apply_to("Fail\n")))
                             .unwrap();
-                        return Err(anyhow::anyhow!("Hash mismatch error!"));
+                        // Don't let the next attempt resume from a corrupt download
+                        if let Err(e) = utils::remove_entry(&tar_path) {
+                            eprintln!("{}", e);
+                        }
+                        return Err(protonctllib::Error::HashMismatch {
+                            expected: sha_string.get(0..128).unwrap_or_default().to_string(),
+                            actual: tar_hash,
+                        }
+                        .into());
                     }
                 }
                 Err(e) => {
-                    return Err(e);
+                    return Err(e.into());
                 }
             }
         }
 
-        // Decompress the file based on the install_type. We may change this later...
-        term.write_fmt(format_args!(
-            "{}",
-            styles.prefix_style.apply_to("Decompressing ... ")
-        ))
-        .unwrap();
-
-        decompress::decompress(&tar_path, &compat_directory)?;
+        // Decompress into the first target and link the result into the others, falling back
+        // to decompressing again where they're on different filesystems
+        let mut installed: Vec<(Target, String)> = Vec::new();
+        let mut first: Option<PathBuf> = None;
+        let mut failure = None;
+        for (target, compat_directory) in destinations {
+            let label = if destinations.len() > 1 {
+                format!("Installing into {} ... ", target)
+            } else {
+                String::from("Decompressing ... ")
+            };
+            term.write_fmt(format_args!("{}", styles.prefix_style.apply_to(label)))
+                .unwrap();
+            // Bottles only picks up wine builds under its own names
+            let rename = match target {
+                Target::Bottles | Target::BottlesFlatpak => {
+                    self.source.directory_name(&release.tag_name, *target)
+                }
+                _ => None,
+            };
+            let linked = first.as_ref().and_then(|first| {
+                decompress::link_install(first, compat_directory, rename.as_deref()).ok()
+            });
+            let directory = match linked {
+                Some(directory) => directory,
+                None => match decompress::install_archive(
+                    &tar_path,
+                    compat_directory,
+                    self.source.install_type(),
+                    rename.as_deref(),
+                ) {
+                    Ok(directory) => directory,
+                    Err(e) => {
+                        term.write_fmt(format_args!("{}", styles.fail_style.apply_to("Fail\n")))
+                            .unwrap();
+                        failure = Some(e);
+                        break;
+                    }
+                },
+            };
+            term.write_fmt(format_args!(
+                "{}",
+                styles.success_style.apply_to("Success\n")
+            ))
+            .unwrap();
+            first.get_or_insert(compat_directory.join(&directory));
+            installed.push((*target, directory));
+        }
 
-        // Nothing has failed and we've reached the end. Remove downloaded files and exit
-        term.write_fmt(format_args!(
-            "{}",
-            styles.success_style.apply_to("Success\n")
-        ))
-        .unwrap();
+        // Remove downloaded files, and record whatever was installed even when a later target
+        // failed so those builds aren't left looking foreign
         term.write_line(format!("{}", styles.prefix_style.apply_to("Removing artifacts")).as_str())
             .unwrap();
-        utils::remove_entry(&tar_path)?;
+        // A leftover archive is only clutter that remove --cache clears
+        if let Err(e) = utils::remove_entry(&tar_path) {
+            eprintln!("{}", e);
+        }
+
+        // Record the installs so list and remove can tell them apart from foreign entries
+        let data_directory = self.config.data_directory()?;
+        let mut manifest = Manifest::load(&data_directory)?;
+        let installed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        for (target, directory) in installed {
+            manifest.add(ManifestEntry {
+                tag: release.tag_name.clone(),
+                source: self.source.to_string(),
+                asset_name: tar_asset.name.clone(),
+                asset_id: tar_asset.id,
+                sha512: tar_hash.clone(),
+                installed_at,
+                target,
+                directory,
+            });
+        }
+        manifest
+            .save(&data_directory)
+            .context("Failed to save install manifest")?;
+        match failure {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+
+    // What install_release would fetch and write, for --dry-run
+    fn plan_release(
+        &self,
+        client: &Client,
+        repo: &str,
+        release: &Release,
+        destinations: &[(Target, PathBuf)],
+    ) -> anyhow::Result<()> {
+        let tar_asset = self.tarball_asset(release)?;
+        let tar_path = self.config.download_path()?.join(&tar_asset.name);
+        would(
+            &self.config,
+            "download",
+            client.asset_url(repo, &tar_asset),
+            Some(tar_asset.size),
+        );
+        would(
+            &self.config,
+            "write",
+            tar_path.display(),
+            Some(tar_asset.size),
+        );
+        if let Some(sha_asset) = self
+            .source
+            .checksum_asset(release)
+            .filter(|_| !self.skip_sha_check)
+        {
+            would(
+                &self.config,
+                "download",
+                client.asset_url(repo, &sha_asset),
+                Some(sha_asset.size),
+            );
+        }
+        for (target, compat_directory) in destinations {
+            let path = match self.source.directory_name(&release.tag_name, *target) {
+                Some(directory) => compat_directory.join(directory).display().to_string(),
+                None => format!(
+                    "{} under the name of the archive's top directory",
+                    compat_directory.display()
+                ),
+            };
+            would(&self.config, "unpack into", path, None);
+        }
+        would(
+            &self.config,
+            "delete",
+            tar_path.display(),
+            Some(tar_asset.size),
+        );
+        would(
+            &self.config,
+            "write",
+            Manifest::path(&self.config.data_path()?).display(),
+            None,
+        );
         Ok(())
     }
+
+    fn tarball_asset(&self, release: &Release) -> anyhow::Result<AssetId> {
+        self.source.tarball_asset(release).ok_or(anyhow::anyhow!(
+            "{} has no {} archive",
+            release.tag_name,
+            self.source
+        ))
+    }
+
+    // Custom sources don't name directories after the tag, so check what the manifest recorded
+    fn is_installed(
+        &self,
+        tag: &str,
+        target: Target,
+        compat_directory: &std::path::Path,
+    ) -> anyhow::Result<bool> {
+        let directory = match self.source.directory_name(tag, target) {
+            Some(directory) => directory,
+            None => {
+                let manifest = Manifest::load(&self.config.data_path()?)
+                    .context("Failed to load install manifest")?;
+                match manifest.find_tag(&self.source.to_string(), target, tag) {
+                    Some(entry) => entry.directory.clone(),
+                    None => return Ok(false),
+                }
+            }
+        };
+        Ok(compat_directory.join(directory).exists())
+    }
+
+    // Resolve latest ( back == 0 ) or latest~N against the releases endpoints. Like
+    // /releases/latest, latest~N only counts published releases, so latest~0 is latest.
+    async fn resolve_latest(
+        &self,
+        client: &Client,
+        repo: &str,
+        back: u8,
+    ) -> anyhow::Result<Release> {
+        if back == 0 {
+            return client
+                .latest_release(repo)
+                .await
+                .context("Failed to get the latest release");
+        }
+        client
+            .releases(repo, Some(MAX_PER_PAGE), Some(1))
+            .await
+            .context("Failed to get releases")?
+            .into_iter()
+            .filter(|r| !r.prerelease && !r.draft)
+            .nth(back as usize)
+            .ok_or(anyhow::anyhow!(
+                "There are fewer than {} {} releases",
+                back + 1,
+                self.source
+            ))
+    }
 }
+
+// Parse latest or latest~N into the number of releases to go back from the newest one.
+// Returns None if the version is a regular release tag.
+fn parse_latest(version: &str) -> anyhow::Result<Option<u8>> {
+    let back = match version.strip_prefix("latest") {
+        Some("") => 0,
+        Some(rest) => match rest.strip_prefix('~').map(str::parse::<u8>) {
+            Some(Ok(n)) if n < MAX_PER_PAGE => n,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Invalid version: {}. Expected latest or latest~N with N < {}",
+                    version,
+                    MAX_PER_PAGE
+                ))
+            }
+        },
+        None => return Ok(None),
+    };
+    Ok(Some(back))
+}
+
+// Download the asset to path. When resume is set and part of the asset is already on disk
+// only the remainder is requested and a failed download is kept for the next attempt.
 async fn handle_install(
     path: &std::path::PathBuf,
-    response: Response,
+    client: &Client,
+    repo: &str,
+    asset: &AssetId,
+    resume: bool,
 ) -> anyhow::Result<std::path::PathBuf> {
-    let content_length = if let Some(c) = response.content_length() {
-        c
+    let mut offset = if resume {
+        std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
     } else {
         0
     };
+    if offset > 0 && offset == asset.size {
+        return Ok(path.to_path_buf());
+    } else if offset > asset.size {
+        offset = 0;
+    }
+
+    let mut response = client.download_asset_from(repo, asset, offset).await?;
+    if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        offset = 0;
+        response = client.download_asset(repo, asset).await?;
+    }
+    if !response.status().is_success() {
+        return Err(Error::DownloadStatus(response.status().as_u16()).into());
+    }
+    // Only append when the server honoured the range, otherwise we're getting the whole file
+    let resumed = offset > 0 && response.status() == StatusCode::PARTIAL_CONTENT;
+    let start = if resumed { offset } else { 0 };
+    let content_length = start + response.content_length().unwrap_or_default();
 
     let pb = ProgressBar::with_draw_target(Some(content_length), ProgressDrawTarget::stderr());
     pb.set_prefix("Downloading:");
     pb.set_style(ProgressStyle::with_template(
         "{prefix:.bold} {wide_bar} {msg:.dim}",
     )?);
+    pb.set_position(start);
 
     let mut file = std::fs::OpenOptions::new()
         .create(true)
         .write(true)
+        .append(resumed)
+        .truncate(!resumed)
         .open(path)
         .context(format!("Failed to open file: {:?}", path))?;
-    let mut total_install = 0;
+    let mut total_install = start;
     let max_hr = indicatif::HumanBytes(content_length);
     let mut stream = response.bytes_stream();
+    let mut result = Ok(());
     while let Some(r) = stream.next().await {
-        if let Ok(bytes) = r {
-            if file.write_all(&bytes).is_ok() {
-                let chunk_size = bytes.len() as u64;
-                total_install += chunk_size;
-                pb.inc(chunk_size);
-                pb.set_message(format!(
-                    "{}/{}",
-                    indicatif::HumanBytes(total_install),
-                    max_hr
-                ));
+        let bytes = match r {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                result = Err(Error::DownloadInterrupted(e));
+                break;
+            }
+        };
+        if let Err(e) = file.write_all(&bytes) {
+            result = Err(Error::io(
+                format!("Failed to write download: {:?}", path),
+                e,
+            ));
+            break;
+        }
+        let chunk_size = bytes.len() as u64;
+        total_install += chunk_size;
+        pb.inc(chunk_size);
+        pb.set_message(format!(
+            "{}/{}",
+            indicatif::HumanBytes(total_install),
+            max_hr
+        ));
+    }
+    if result.is_ok() && total_install != asset.size {
+        result = Err(Error::SizeMismatch {
+            expected: asset.size,
+            actual: total_install,
+        });
+    }
+
+    if let Err(e) = result {
+        pb.abandon();
+        drop(file);
+        if !resume {
+            if let Err(e) = utils::remove_entry(path) {
+                eprintln!("{}", e);
             }
         }
+        return Err(e.into());
     }
     pb.finish();
     Ok(path.to_path_buf())
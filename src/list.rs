@@ -1,21 +1,28 @@
-use crate::cli::InstallTypeCmd;
 use crate::cli_utils::Run;
+use crate::steam;
 use anyhow::Context;
 use async_trait::async_trait;
 use console::{Style, Term};
 use protonctllib::{
+    config::{self, Config},
     github::api::Release,
+    manifest::Manifest,
+    source::Source,
+    target::Target,
+    version::Version,
     version_info::{get_installed_versions, get_releases_paged},
 };
+use std::cmp::Ordering;
 use std::io::Write;
 
-#[derive(Debug, Eq, Ord, PartialEq, PartialOrd, Default)]
+#[derive(Debug, Eq, PartialEq, Default)]
 pub struct List {
     pub number: u8,
     pub page: u8,
     pub local: bool,
-    pub flatpak: bool,
-    pub install_type: InstallTypeCmd,
+    pub targets: Vec<Target>,
+    pub source: Source,
+    pub config: Config,
 }
 
 impl List {
@@ -23,15 +30,17 @@ impl List {
         number: u8,
         page: u8,
         local: bool,
-        flatpak: bool,
-        install_type: InstallTypeCmd,
+        targets: Vec<Target>,
+        source: Source,
+        config: Config,
     ) -> Self {
         Self {
             number,
             page,
             local,
-            flatpak,
-            install_type,
+            targets,
+            source,
+            config,
         }
     }
 }
@@ -44,12 +53,12 @@ struct Styles {
 }
 
 impl Styles {
-    pub fn new() -> Self {
+    pub fn new(styles: &config::Styles) -> Self {
         Self {
-            prefix_style: Style::new().bold(),
-            version_style: Style::new().green(),
-            url_style: Style::new().blue().underlined(),
-            change_log_style: Style::new().dim(),
+            prefix_style: Style::from_dotted_str(&styles.label),
+            version_style: Style::from_dotted_str(&styles.version),
+            url_style: Style::from_dotted_str(&styles.url),
+            change_log_style: Style::from_dotted_str(&styles.changelog),
         }
     }
 }
@@ -59,7 +68,9 @@ impl Run for List {
     async fn run(&self) -> anyhow::Result<()> {
         let mut term = Term::buffered_stdout();
         if self.local {
-            self.list_local(&mut term)?;
+            for target in &self.targets {
+                self.list_local(&mut term, *target)?;
+            }
         } else {
             self.list_remote(&mut term).await?;
         }
@@ -69,43 +80,83 @@ impl Run for List {
 }
 
 impl List {
-    fn list_local(&self, term: &mut Term) -> anyhow::Result<()> {
-        let directory_style = Style::new().blue();
-        let style_header = Style::new().bold().underlined();
-        let versions = get_installed_versions(
+    fn list_local(&self, term: &mut Term, target: Target) -> anyhow::Result<()> {
+        let directory_style = Style::from_dotted_str(&self.config.styles.directory);
+        let style_header = Style::from_dotted_str(&self.config.styles.header);
+        let status_style = Style::from_dotted_str(&self.config.styles.status);
+        let manifest = Manifest::load(&self.config.data_directory()?)
+            .context("Failed to load install manifest")?;
+        let mut versions: Vec<(Option<Version>, String)> = get_installed_versions(
             &self
-                .install_type
-                .get_compat_directory_safe(self.flatpak)
+                .source
+                .get_compat_directory_safe(&self.config, target)
                 .context("Failed to get compatibility directory")?,
         )
-        .context("Failed to get directory entries")?;
-        let header_str = style_header
-            .apply_to(format!("{} installs:", &self.install_type))
-            .to_string();
+        .context("Failed to get directory entries")?
+        .into_iter()
+        .filter_map(|e| match e.file_name().into_string() {
+            Ok(name) => Some((self.source.version_of_directory(&name), name)),
+            Err(_) => {
+                eprintln!("Failed to convert file_name to string");
+                None
+            }
+        })
+        .collect();
+        // Newest releases first, followed by anything we couldn't parse in name order
+        versions.sort_by(|a, b| match (&a.0, &b.0) {
+            (Some(a), Some(b)) => b.cmp(a),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => a.1.cmp(&b.1),
+        });
+        // Only name the target when listing more than one
+        let header = if self.targets.len() > 1 {
+            format!("{} installs ({}):", &self.source, target)
+        } else {
+            format!("{} installs:", &self.source)
+        };
+        let header_str = style_header.apply_to(header).to_string();
         term.write_line(&header_str).unwrap();
-        for version in versions {
-            let version = version.file_name();
-            if let Some(name) = version.to_str() {
-                let name = name.to_string();
-                term.write_fmt(format_args!("{}\n", directory_style.apply_to(name)))
-                    .unwrap();
+        let source = self.source.to_string();
+        let compat_directory = self
+            .source
+            .compat_directory(&self.config, target)
+            .context("Failed to get compatibility directory")?;
+        // Usage is only shown here, so an unreadable config is a warning
+        let assignments = steam::assignments(&self.source, target).unwrap_or_else(|e| {
+            eprintln!("{:#}", e);
+            Vec::new()
+        });
+        for (_, name) in versions {
+            let status = if manifest.is_managed(&source, target, &name) {
+                "managed"
             } else {
-                eprintln!("Failed to convert file_name to string");
+                "foreign"
+            };
+            term.write_fmt(format_args!(
+                "{} {}\n",
+                directory_style.apply_to(&name),
+                status_style.apply_to(format!("({})", status))
+            ))
+            .unwrap();
+            for assignment in
+                protonctllib::steam::assigned_to(&assignments, &compat_directory.join(&name))
+            {
+                term.write_line(&format!("    {}", assignment)).unwrap();
             }
         }
         Ok(())
     }
 
     async fn list_remote(&self, term: &mut Term) -> anyhow::Result<()> {
-        if let Some(releases) =
-            get_releases_paged(self.install_type.get_url(false), self.number, self.page).await
-        {
-            let styles = Styles::new();
-            for release in releases {
-                print_release(term, &styles, &release);
-            }
-        } else {
-            return Err(anyhow::anyhow!("Failed to get releases"));
+        let client = self.config.client()?;
+        let releases =
+            get_releases_paged(&client, &self.source.repository(), self.number, self.page)
+                .await
+                .context("Failed to get releases")?;
+        let styles = Styles::new(&self.config.styles);
+        for release in releases {
+            print_release(term, &styles, &release);
         }
         Ok(())
     }
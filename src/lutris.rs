@@ -0,0 +1,196 @@
+use crate::cli_utils::{would, Run};
+use anyhow::Context;
+use async_trait::async_trait;
+use console::{Style, Term};
+use protonctllib::{
+    config::Config,
+    install_type::InstallType,
+    lutris::{self, LutrisConfig},
+    source::Source,
+    target::Target,
+    version_info,
+};
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum LutrisAction {
+    SetVersion(String, String),
+    UnsetVersion(String),
+    Default(String),
+    Show,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct LutrisCmd {
+    pub action: LutrisAction,
+    pub target: Target,
+    pub dry_run: bool,
+    pub config: Config,
+}
+
+impl LutrisCmd {
+    pub fn new(action: LutrisAction, target: Target, dry_run: bool, config: Config) -> Self {
+        Self {
+            action,
+            target,
+            dry_run,
+            config,
+        }
+    }
+}
+
+#[async_trait]
+impl Run for LutrisCmd {
+    async fn run(&self) -> anyhow::Result<()> {
+        let term = Term::stdout();
+        let prefix_style = Style::from_dotted_str(&self.config.styles.prefix);
+        let version_style = Style::from_dotted_str(&self.config.styles.version);
+        let label_style = Style::from_dotted_str(&self.config.styles.label);
+
+        let root = lutris::root_for(self.target)?;
+        let games = lutris::wine_games(&root).context("Failed to read the Lutris game list")?;
+        let (lutris_config, name) = match &self.action {
+            LutrisAction::Show => {
+                let runner = LutrisConfig::runner(&root)?;
+                let default = runner.wine_version().unwrap_or("Lutris' own default");
+                term.write_line(&format!(
+                    "{}: {}",
+                    label_style.apply_to("Default"),
+                    version_style.apply_to(default)
+                ))
+                .unwrap();
+                for game in &games {
+                    let game_config = match LutrisConfig::load(&game.config) {
+                        Ok(game_config) => game_config,
+                        Err(e) => {
+                            eprintln!("Skipping {}: {}", game.name, e);
+                            continue;
+                        }
+                    };
+                    term.write_line(&format!(
+                        "{}: {}",
+                        label_style.apply_to(format!("{} ({})", game.name, game.slug)),
+                        version_style.apply_to(game_config.wine_version().unwrap_or("default"))
+                    ))
+                    .unwrap();
+                }
+                return Ok(());
+            }
+            LutrisAction::SetVersion(game, version) => {
+                let game = lutris::find_game(&games, game)?;
+                let mut game_config = LutrisConfig::load(&game.config)?;
+                let version = self.find_version(version)?;
+                game_config.set_wine_version(&version);
+                term.write_line(&format!(
+                    "{} {}",
+                    prefix_style.apply_to(format!("{} now uses", game.name)),
+                    version_style.apply_to(version)
+                ))
+                .unwrap();
+                (game_config, game.name.as_str())
+            }
+            LutrisAction::UnsetVersion(game) => {
+                let game = lutris::find_game(&games, game)?;
+                let mut game_config = LutrisConfig::load(&game.config)?;
+                if !game_config.unset_wine_version() {
+                    eprintln!("{} has no wine version set", game.name);
+                    return Ok(());
+                }
+                term.write_line(&format!(
+                    "{}",
+                    prefix_style.apply_to(format!("{} uses the default wine version", game.name))
+                ))
+                .unwrap();
+                (game_config, game.name.as_str())
+            }
+            LutrisAction::Default(version) => {
+                let mut runner = LutrisConfig::runner(&root)?;
+                let version = self.find_version(version)?;
+                runner.set_wine_version(&version);
+                term.write_line(&format!(
+                    "{} {} {}",
+                    prefix_style.apply_to("Lutris now uses"),
+                    version_style.apply_to(version),
+                    prefix_style.apply_to(format!("by default ({})", self.target))
+                ))
+                .unwrap();
+                (runner, "the wine runner")
+            }
+        };
+        if self.dry_run {
+            would(&self.config, "write", lutris_config.path.display(), None);
+            return Ok(());
+        }
+        let backup = lutris_config
+            .save()
+            .with_context(|| format!("Failed to save the config of {}", name))?;
+        if let Some(backup) = backup {
+            term.write_line(&format!(
+                "{} {}",
+                prefix_style.apply_to("Backed up the previous config to"),
+                backup.display()
+            ))
+            .unwrap();
+        }
+        Ok(())
+    }
+}
+
+// The wine builds set for the games in a target's Lutris and its default, as (game, runner
+// directory)
+pub(crate) fn assignments(target: Target) -> anyhow::Result<Vec<(String, String)>> {
+    if !lutris::TARGETS.contains(&target) {
+        return Ok(Vec::new());
+    }
+    let read = || -> protonctllib::Result<Vec<(String, String)>> {
+        let root = lutris::root_for(target)?;
+        let mut assignments = Vec::new();
+        if let Some(version) = LutrisConfig::runner(&root)?.wine_version() {
+            assignments.push((String::from("Default"), version.to_string()));
+        }
+        // A broken game config only loses that game, as Lutris can't run it either
+        for game in lutris::wine_games(&root)? {
+            let game_config = match LutrisConfig::load(&game.config) {
+                Ok(game_config) => game_config,
+                Err(e) => {
+                    eprintln!("Skipping {}: {}", game.name, e);
+                    continue;
+                }
+            };
+            if let Some(version) = game_config.wine_version() {
+                assignments.push((game.name, version.to_string()));
+            }
+        }
+        Ok(assignments)
+    };
+    read().context("Failed to read which games use each wine version")
+}
+
+impl LutrisCmd {
+    // Lutris goes by the runner directory name, so tags are turned into the directory they
+    // were installed to. Builds Lutris manages itself aren't ours to check, so anything else is
+    // used as given.
+    fn find_version(&self, version: &str) -> anyhow::Result<String> {
+        let source = Source::from(InstallType::Wine);
+        let compat_directory = source.compat_directory(&self.config, self.target)?;
+        let installed = version_info::get_installed_versions(&compat_directory).unwrap_or_default();
+        let found = installed
+            .iter()
+            .filter_map(|e| e.file_name().into_string().ok())
+            .find(|name| {
+                name == version
+                    || source
+                        .version_of_directory(name)
+                        .is_some_and(|v| v.tag() == version)
+            });
+        Ok(match found {
+            Some(name) => name,
+            None => {
+                eprintln!(
+                    "{:?} isn't installed in {:?}. Using the name as given",
+                    version, compat_directory
+                );
+                version.to_string()
+            }
+        })
+    }
+}
@@ -1,13 +1,28 @@
 pub mod cli;
 pub mod cli_utils;
+pub mod config;
 pub mod install;
 pub mod list;
+mod lutris;
+mod prune;
 pub mod remove;
+mod steam;
+pub mod update;
+
+use std::process::ExitCode;
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
+async fn main() -> ExitCode {
     let cmd = cli::build_cli();
-    let runner = cli_utils::command_to_struct(&cmd)?;
-    runner.run().await?;
-    Ok(())
+    let result = match cli_utils::command_to_struct(&cmd) {
+        Ok(runner) => runner.run().await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            cli_utils::exit_code(&e)
+        }
+    }
 }
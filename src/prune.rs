@@ -0,0 +1,180 @@
+use crate::cli_utils::{confirm, print_builds, would, Run};
+use crate::remove::Usage;
+use anyhow::Context;
+use async_trait::async_trait;
+use console::{Style, Term};
+use protonctllib::{
+    config::Config, manifest::Manifest, source::Source, target::Target, utils, version::Version,
+    version_info,
+};
+use std::path::PathBuf;
+
+#[derive(Debug, Eq, PartialEq, Default)]
+pub struct Prune {
+    pub keep: usize,
+    pub pins: Vec<String>,
+    pub force: bool,
+    pub yes: bool,
+    pub dry_run: bool,
+    pub targets: Vec<Target>,
+    pub source: Source,
+    pub config: Config,
+}
+
+impl Prune {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        keep: usize,
+        pins: Vec<String>,
+        force: bool,
+        yes: bool,
+        dry_run: bool,
+        targets: Vec<Target>,
+        source: Source,
+        config: Config,
+    ) -> Self {
+        Self {
+            keep,
+            pins,
+            force,
+            yes,
+            dry_run,
+            targets,
+            source,
+            config,
+        }
+    }
+}
+
+// A managed build that would be deleted
+struct Candidate {
+    target: Target,
+    name: String,
+    path: PathBuf,
+    size: u64,
+}
+
+#[async_trait]
+impl Run for Prune {
+    async fn run(&self) -> anyhow::Result<()> {
+        let term = Term::stdout();
+        let prefix_style = Style::from_dotted_str(&self.config.styles.prefix);
+
+        let data_directory = if self.dry_run {
+            self.config.data_path()?
+        } else {
+            self.config.data_directory()?
+        };
+        let mut manifest =
+            Manifest::load(&data_directory).context("Failed to load install manifest")?;
+        let mut candidates = Vec::new();
+        for target in &self.targets {
+            candidates.extend(self.candidates(&manifest, *target)?);
+        }
+        if candidates.is_empty() {
+            term.write_line(&format!("{}", prefix_style.apply_to("Nothing to prune")))
+                .unwrap();
+            return Ok(());
+        }
+
+        let builds: Vec<(&str, Target, u64)> = candidates
+            .iter()
+            .map(|c| (c.name.as_str(), c.target, c.size))
+            .collect();
+        print_builds(&self.config, &builds);
+
+        if self.dry_run {
+            for candidate in &candidates {
+                would(
+                    &self.config,
+                    "delete",
+                    candidate.path.display(),
+                    Some(candidate.size),
+                );
+            }
+            return Ok(());
+        }
+        if !self.yes && !confirm(&format!("Remove {} builds?", candidates.len()))? {
+            term.write_line(&format!("{}", prefix_style.apply_to("Nothing removed")))
+                .unwrap();
+            return Ok(());
+        }
+        // Builds stay in the manifest until they're gone so a failed delete can be retried
+        let source = self.source.to_string();
+        let mut failed = 0;
+        for candidate in candidates {
+            match utils::remove_entry(&candidate.path) {
+                Ok(()) => {
+                    manifest.remove(&source, candidate.target, &candidate.name);
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    failed += 1;
+                }
+            }
+        }
+        manifest
+            .save(&data_directory)
+            .context("Failed to save install manifest")?;
+        if failed > 0 {
+            return Err(anyhow::anyhow!("Failed to remove {} builds", failed));
+        }
+        Ok(())
+    }
+}
+
+impl Prune {
+    // The managed builds in a target past the newest keep, leaving out the pinned ones and,
+    // unless forced, the ones games still use. Foreign builds are never touched.
+    fn candidates(&self, manifest: &Manifest, target: Target) -> anyhow::Result<Vec<Candidate>> {
+        let compat_directory = self.source.compat_directory(&self.config, target)?;
+        if !compat_directory.exists() {
+            return Ok(Vec::new());
+        }
+        let source = self.source.to_string();
+        // Builds are ordered by version where the name can be parsed and by install time
+        // otherwise, newest first
+        let mut managed: Vec<(Option<Version>, u64, String, String)> =
+            version_info::get_installed_versions(&compat_directory)
+                .context("Failed to get directory entries")?
+                .into_iter()
+                .filter_map(|e| {
+                    let name = e.file_name().into_string().ok()?;
+                    let entry = manifest.find(&source, target, &name)?;
+                    Some((
+                        self.source.version_of_directory(&name),
+                        entry.installed_at,
+                        name,
+                        entry.tag.clone(),
+                    ))
+                })
+                .collect();
+        managed.sort_by(|a, b| (&b.0, b.1).cmp(&(&a.0, a.1)));
+
+        let usage = Usage::load(&self.source, target, self.force)?;
+        let mut candidates = Vec::new();
+        for (_, _, name, tag) in managed.into_iter().skip(self.keep) {
+            let path = compat_directory.join(&name);
+            if self.pins.iter().any(|p| *p == name || *p == tag) {
+                eprintln!("Keeping {:?}: pinned", name);
+                continue;
+            }
+            let used_by = usage.used_by(&path);
+            if !used_by.is_empty() && !self.force {
+                eprintln!(
+                    "Keeping {:?}: used by {}. Use --force to remove it anyway",
+                    name,
+                    used_by.join(", ")
+                );
+                continue;
+            }
+            candidates.push(Candidate {
+                target,
+                size: utils::directory_size(&path),
+                name,
+                path,
+            });
+        }
+        Ok(candidates)
+    }
+}
@@ -1,31 +1,50 @@
-use crate::cli::InstallTypeCmd;
-use crate::cli_utils::Run;
+use crate::cli_utils::{confirm, print_builds, would, Run};
+use crate::{lutris, steam};
+use anyhow::Context;
 use async_trait::async_trait;
-use protonctllib::{utils, version_info};
+use console::{Style, Term};
+use protonctllib::{
+    config::Config, constants, install_type::InstallType, manifest::Manifest, source::Source,
+    steam::Assignment, target::Target, utils, version_info,
+};
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Eq, Ord, PartialEq, PartialOrd, Default)]
+#[derive(Debug, Eq, PartialEq, Default)]
 pub struct Remove {
     pub cache: bool,
     pub all: bool,
-    pub flatpak: bool,
-    pub install_type: InstallTypeCmd,
+    pub targets: Vec<Target>,
+    pub source: Source,
     pub pw_version: String,
+    pub force: bool,
+    pub yes: bool,
+    pub dry_run: bool,
+    pub config: Config,
 }
 
 impl Remove {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         cache: bool,
         all: bool,
-        flatpak: bool,
-        install_type: InstallTypeCmd,
+        targets: Vec<Target>,
+        source: Source,
         pw_version: String,
+        force: bool,
+        yes: bool,
+        dry_run: bool,
+        config: Config,
     ) -> Self {
         Self {
             cache,
             all,
-            flatpak,
-            install_type,
+            targets,
+            source,
             pw_version,
+            force,
+            yes,
+            dry_run,
+            config,
         }
     }
 }
@@ -33,25 +52,256 @@ impl Remove {
 #[async_trait]
 impl Run for Remove {
     async fn run(&self) -> anyhow::Result<()> {
+        if self.cache {
+            return self.remove_cache();
+        }
+
+        let data_directory = if self.dry_run {
+            self.config.data_path()?
+        } else {
+            self.config.data_directory()?
+        };
+        let mut manifest =
+            Manifest::load(&data_directory).context("Failed to load install manifest")?;
+        let mut found = false;
+        let mut removals = Vec::new();
+        for target in &self.targets {
+            found |= self.remove_from(&manifest, *target, &mut removals)?;
+        }
+        if !self.all && !found {
+            eprintln!("{:?} not found", self.pw_version);
+        }
+        if self.dry_run {
+            for (_, _, path) in &removals {
+                would(
+                    &self.config,
+                    "delete",
+                    path.display(),
+                    Some(utils::directory_size(path)),
+                );
+            }
+            return Ok(());
+        }
+        // Deleting everything is hard to take back, so show what goes first
+        if self.all && !self.yes && !removals.is_empty() {
+            let builds: Vec<(&str, Target, u64)> = removals
+                .iter()
+                .map(|(target, name, path)| (name.as_str(), *target, utils::directory_size(path)))
+                .collect();
+            print_builds(&self.config, &builds);
+            if !confirm(&format!("Remove {} builds?", removals.len()))? {
+                let prefix_style = Style::from_dotted_str(&self.config.styles.prefix);
+                Term::stdout()
+                    .write_line(&format!("{}", prefix_style.apply_to("Nothing removed")))
+                    .unwrap();
+                return Ok(());
+            }
+        }
+        // Builds stay in the manifest until they're gone so a failed delete can be retried
+        let source = self.source.to_string();
+        let mut failed = 0;
+        for (target, name, path) in removals {
+            match utils::remove_entry(&path) {
+                Ok(()) => {
+                    manifest.remove(&source, target, &name);
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    failed += 1;
+                }
+            }
+        }
+        manifest
+            .save(&data_directory)
+            .context("Failed to save install manifest")?;
+        if failed > 0 {
+            return Err(anyhow::anyhow!("Failed to remove {} builds", failed));
+        }
+        Ok(())
+    }
+}
+
+impl Remove {
+    fn remove_cache(&self) -> anyhow::Result<()> {
+        // The manifest isn't a download artifact, in case both directories are the same
+        if !self.dry_run {
+            let download_directory = self.config.download_directory()?;
+            utils::remove_all_in_except(&download_directory, &[constants::MANIFEST_FILE])?;
+            return Ok(());
+        }
+        let download_directory = self.config.download_path()?;
+        let Ok(entries) = std::fs::read_dir(&download_directory) else {
+            return Ok(());
+        };
+        for entry in entries.flatten() {
+            if entry.file_name() == constants::MANIFEST_FILE {
+                continue;
+            }
+            let path = entry.path();
+            would(
+                &self.config,
+                "delete",
+                path.display(),
+                Some(utils::directory_size(&path)),
+            );
+        }
+        Ok(())
+    }
+
+    // Collect the matching installs of one target that may be removed as (target, name, path).
+    // Returns whether anything matched
+    fn remove_from(
+        &self,
+        manifest: &Manifest,
+        target: Target,
+        removals: &mut Vec<(Target, String, PathBuf)>,
+    ) -> anyhow::Result<bool> {
+        let compat_path = if self.dry_run {
+            self.source.compat_directory(&self.config, target)?
+        } else {
+            self.source
+                .get_compat_directory_safe(&self.config, target)?
+        };
+        if !compat_path.exists() {
+            return Ok(self.all);
+        }
+        let installed_versions = version_info::get_installed_versions(&compat_path)?;
+        let usage = Usage::load(&self.source, target, self.force)?;
         if self.all {
-            let compat_path = self.install_type.get_compat_directory_safe(self.flatpak)?;
-            utils::remove_all_in(&compat_path)?;
-        } else if self.cache {
-            let install_path = utils::get_download_directory_safe()?;
-            utils::remove_all_in(&install_path)?;
+            for item in installed_versions {
+                if let Some(name) = item.file_name().to_str() {
+                    if may_remove(
+                        manifest,
+                        &self.source,
+                        target,
+                        &item.path(),
+                        &usage,
+                        self.force,
+                    ) {
+                        removals.push((target, name.to_string(), item.path()));
+                    }
+                }
+            }
+            Ok(true)
         } else {
-            let mut compat_path = self.install_type.get_compat_directory_safe(self.flatpak)?;
-            let installed_versions = version_info::get_installed_versions(&compat_path)?;
-            compat_path.push(&self.pw_version);
-            if let Some(item) = installed_versions
-                .into_iter()
-                .find(|e| e.path() == compat_path)
-            {
-                utils::remove_entry(&item.path())?;
+            // Match the directory name first and fall back to the release tag it was installed from
+            if let Some(item) = installed_versions.iter().find(|e| {
+                e.file_name() == self.pw_version.as_str()
+                    || e.file_name().to_str().is_some_and(|name| {
+                        self.source
+                            .version_of_directory(name)
+                            .is_some_and(|v| v.tag() == self.pw_version)
+                    })
+                    || e.file_name().to_str().is_some_and(|name| {
+                        manifest
+                            .find(&self.source.to_string(), target, name)
+                            .is_some_and(|entry| entry.tag == self.pw_version)
+                    })
+            }) {
+                if let Some(name) = item.file_name().to_str() {
+                    if may_remove(
+                        manifest,
+                        &self.source,
+                        target,
+                        &item.path(),
+                        &usage,
+                        self.force,
+                    ) {
+                        removals.push((target, name.to_string(), item.path()));
+                    }
+                }
+                Ok(true)
             } else {
-                eprintln!("{:?} not found", self.pw_version);
+                Ok(false)
             }
         }
-        Ok(())
+    }
+}
+
+// Only remove entries protonctl installed and no game uses unless force is set
+pub(crate) fn may_remove(
+    manifest: &Manifest,
+    source: &Source,
+    target: Target,
+    path: &Path,
+    usage: &Usage,
+    force: bool,
+) -> bool {
+    if force {
+        return true;
+    }
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    let used_by = usage.used_by(path);
+    if !used_by.is_empty() {
+        eprintln!(
+            "Skipping {:?}: used by {}. Use --force to remove it anyway",
+            name,
+            used_by.join(", ")
+        );
+        return false;
+    }
+    if manifest.find(&source.to_string(), target, name).is_none() {
+        eprintln!(
+            "Skipping {:?}: not installed by protonctl. Use --force to remove it anyway",
+            name
+        );
+        return false;
+    }
+    true
+}
+
+// The games that use the builds of a target, as Steam and Lutris have them set
+#[derive(Default)]
+pub(crate) struct Usage {
+    steam: Vec<Assignment>,
+    lutris: Vec<(String, String)>,
+}
+
+impl Usage {
+    // Removing builds without knowing which are in use could break games, so failing to read
+    // it is an error unless forced
+    pub(crate) fn load(source: &Source, target: Target, force: bool) -> anyhow::Result<Self> {
+        let read = || -> anyhow::Result<Self> {
+            Ok(Self {
+                steam: steam::assignments(source, target)?,
+                // Lutris only runs wine builds, which custom sources may also be
+                lutris: match source.install_type() {
+                    Some(InstallType::Proton | InstallType::ULWGL) => Vec::new(),
+                    _ => lutris::assignments(target)?,
+                },
+            })
+        };
+        match read() {
+            Ok(usage) => Ok(usage),
+            Err(e) if force => {
+                eprintln!("{:#}", e);
+                Ok(Self::default())
+            }
+            Err(e) => Err(
+                e.context("Can't tell which builds games use. Use --force to remove them anyway")
+            ),
+        }
+    }
+
+    // The games set to use the build at path
+    pub(crate) fn used_by(&self, path: &Path) -> Vec<String> {
+        let mut games: Vec<String> = protonctllib::steam::assigned_to(&self.steam, path)
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        games.extend(
+            self.lutris
+                .iter()
+                .filter(|(_, version)| version == name)
+                .map(|(game, _)| game.clone()),
+        );
+        games
     }
 }
@@ -0,0 +1,194 @@
+use crate::cli_utils::{would, Run};
+use anyhow::Context;
+use async_trait::async_trait;
+use console::{Style, Term};
+use protonctllib::{
+    config::Config,
+    install_type::InstallType,
+    source::Source,
+    steam::{self, Assignment, SteamConfig},
+    target::Target,
+};
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum SteamAction {
+    SetTool(String, String),
+    UnsetTool(String),
+    Default(String),
+    Show,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct SteamCmd {
+    pub action: SteamAction,
+    pub target: Target,
+    pub force: bool,
+    pub dry_run: bool,
+    pub config: Config,
+}
+
+impl SteamCmd {
+    pub fn new(
+        action: SteamAction,
+        target: Target,
+        force: bool,
+        dry_run: bool,
+        config: Config,
+    ) -> Self {
+        Self {
+            action,
+            target,
+            force,
+            dry_run,
+            config,
+        }
+    }
+}
+
+#[async_trait]
+impl Run for SteamCmd {
+    async fn run(&self) -> anyhow::Result<()> {
+        let term = Term::stdout();
+        let prefix_style = Style::from_dotted_str(&self.config.styles.prefix);
+        let version_style = Style::from_dotted_str(&self.config.styles.version);
+        let label_style = Style::from_dotted_str(&self.config.styles.label);
+
+        let root = steam::root_for(self.target)?;
+        let mut steam_config = SteamConfig::load(&root).context("Failed to load Steam config")?;
+        let apps = steam::installed_apps(&root).context("Failed to read Steam libraries")?;
+        let app_name = |id: &str| match apps.iter().find(|a| a.id == id) {
+            Some(app) => format!("{} ({})", app.name, id),
+            None if id == "0" => String::from("Default"),
+            None => format!("Unknown app ({})", id),
+        };
+        match &self.action {
+            SteamAction::Show => {
+                for tool in steam_config.compat_tools() {
+                    term.write_line(&format!(
+                        "{}: {}",
+                        label_style.apply_to(app_name(&tool.app_id)),
+                        version_style.apply_to(&tool.name)
+                    ))
+                    .unwrap();
+                }
+                return Ok(());
+            }
+            SteamAction::SetTool(app, tool) => {
+                check_running(self.force || self.dry_run)?;
+                let app_id = steam::find_app_id(&apps, app)?;
+                // Tools that ship with Steam such as proton_experimental aren't in
+                // compatibilitytools.d, so names that aren't installed are used as given
+                let compat_directory = Source::from(InstallType::Proton)
+                    .compat_directory(&self.config, self.target)?;
+                let tool = match steam::find_tool(&compat_directory, tool) {
+                    Some(name) => name,
+                    None => {
+                        eprintln!(
+                            "{:?} isn't installed in {:?}. Using the name as given",
+                            tool, compat_directory
+                        );
+                        tool.clone()
+                    }
+                };
+                steam_config.set_compat_tool(&app_id, &tool);
+                term.write_line(&format!(
+                    "{} {} {}",
+                    prefix_style.apply_to(format!("{} now uses", app_name(&app_id))),
+                    version_style.apply_to(tool),
+                    prefix_style.apply_to("after Steam restarts")
+                ))
+                .unwrap();
+            }
+            SteamAction::Default(tool) => {
+                check_running(self.force || self.dry_run)?;
+                return set_default_tool(&self.config, self.target, tool, self.dry_run);
+            }
+            SteamAction::UnsetTool(app) => {
+                check_running(self.force || self.dry_run)?;
+                let app_id = steam::find_app_id(&apps, app)?;
+                if !steam_config.unset_compat_tool(&app_id) {
+                    eprintln!("{} has no compatibility tool set", app_name(&app_id));
+                    return Ok(());
+                }
+                term.write_line(&format!(
+                    "{}",
+                    prefix_style.apply_to(format!(
+                        "{} uses Steam's default tool after Steam restarts",
+                        app_name(&app_id)
+                    ))
+                ))
+                .unwrap();
+            }
+        }
+        save(&steam_config, &self.config, self.dry_run)
+    }
+}
+
+// Steam overwrites its config on exit, which would undo the change
+pub(crate) fn check_running(force: bool) -> anyhow::Result<()> {
+    if !force && steam::is_running() {
+        return Err(anyhow::anyhow!(
+            "Steam is running and will overwrite the change when it exits. Close Steam or use --force"
+        ));
+    }
+    Ok(())
+}
+
+// The games set to use tools in a target's Steam. Wine builds aren't Steam tools
+pub(crate) fn assignments(source: &Source, target: Target) -> anyhow::Result<Vec<Assignment>> {
+    if matches!(
+        source.install_type(),
+        Some(InstallType::Wine | InstallType::ULWGL)
+    ) || !steam::TARGETS.contains(&target)
+    {
+        return Ok(Vec::new());
+    }
+    steam::root_for(target)
+        .and_then(|root| steam::assignments(&root))
+        .context("Failed to read which games use each tool")
+}
+
+// Make an installed tool, given by directory or internal name, the Steam Play default for
+// every game in a target's Steam
+pub(crate) fn set_default_tool(
+    config: &Config,
+    target: Target,
+    tool: &str,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let prefix_style = Style::from_dotted_str(&config.styles.prefix);
+    let version_style = Style::from_dotted_str(&config.styles.version);
+    let compat_directory = Source::from(InstallType::Proton).compat_directory(config, target)?;
+    let name = steam::find_tool(&compat_directory, tool).ok_or(protonctllib::Error::NotFound(
+        format!("compatibility tool {:?} in {:?}", tool, compat_directory),
+    ))?;
+    let mut steam_config =
+        SteamConfig::load(&steam::root_for(target)?).context("Failed to load Steam config")?;
+    steam_config.set_compat_tool("0", &name);
+    Term::stdout()
+        .write_line(&format!(
+            "{} {} {}",
+            prefix_style.apply_to("Steam now uses"),
+            version_style.apply_to(&name),
+            prefix_style.apply_to(format!("by default ({}) after it restarts", target))
+        ))
+        .unwrap();
+    save(&steam_config, config, dry_run)
+}
+
+fn save(steam_config: &SteamConfig, config: &Config, dry_run: bool) -> anyhow::Result<()> {
+    if dry_run {
+        would(config, "write", steam_config.path.display(), None);
+        return Ok(());
+    }
+    let prefix_style = Style::from_dotted_str(&config.styles.prefix);
+    let backup = steam_config.save().context("Failed to save Steam config")?;
+    Term::stdout()
+        .write_line(&format!(
+            "{} {}",
+            prefix_style.apply_to("Backed up the previous config to"),
+            backup.display()
+        ))
+        .unwrap();
+    Ok(())
+}
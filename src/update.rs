@@ -0,0 +1,229 @@
+use crate::cli_utils::{would, Run};
+use crate::install::Install;
+use crate::remove::{may_remove, Usage};
+use anyhow::Context;
+use async_trait::async_trait;
+use console::{Style, Term};
+use protonctllib::{
+    config::Config, github::api::Release, manifest::Manifest, source::Source, target::Target,
+    utils, version::Version, version_info,
+};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Eq, PartialEq, Default)]
+pub struct Update {
+    pub targets: Vec<Target>,
+    pub skip_sha_check: bool,
+    pub remove_old: bool,
+    pub force: bool,
+    pub source: Source,
+    pub resume: bool,
+    pub dry_run: bool,
+    pub config: Config,
+}
+
+impl Update {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        targets: Vec<Target>,
+        skip_sha_check: bool,
+        remove_old: bool,
+        force: bool,
+        source: Source,
+        resume: bool,
+        dry_run: bool,
+        config: Config,
+    ) -> Self {
+        Self {
+            targets,
+            skip_sha_check,
+            remove_old,
+            force,
+            source,
+            resume,
+            dry_run,
+            config,
+        }
+    }
+}
+
+#[async_trait]
+impl Run for Update {
+    async fn run(&self) -> anyhow::Result<()> {
+        let term = Term::stderr();
+        let prefix_style = Style::from_dotted_str(&self.config.styles.prefix);
+        let version_style = Style::from_dotted_str(&self.config.styles.success);
+
+        let client = self.config.client()?;
+        let repo = self.source.repository();
+        let release = client
+            .latest_release(&repo)
+            .await
+            .context("Failed to get the latest release")?;
+        let data_directory = if self.dry_run {
+            self.config.data_path()?
+        } else {
+            self.config.data_directory()?
+        };
+        // Targets missing the newest release, and the installs it supersedes in each target
+        // with what uses them, read before downloading so an unreadable config stops early
+        let mut outdated = Vec::new();
+        let mut superseded = Vec::new();
+        let mut usages = Vec::new();
+        for target in &self.targets {
+            let compat_directory = if self.dry_run {
+                self.source.compat_directory(&self.config, *target)
+            } else {
+                self.source.get_compat_directory_safe(&self.config, *target)
+            }
+            .context("Failed to get compatibility directory")?;
+            let (up_to_date, old) =
+                self.check_target(&release, *target, &compat_directory, &data_directory)?;
+            if !up_to_date {
+                outdated.push((*target, compat_directory));
+            }
+            superseded.extend(old.into_iter().map(|(name, path)| (*target, name, path)));
+            if self.remove_old {
+                usages.push((*target, Usage::load(&self.source, *target, self.force)?));
+            }
+        }
+        if outdated.is_empty() {
+            term.write_line(
+                format!(
+                    "{} {}",
+                    prefix_style.apply_to("Already up to date:"),
+                    version_style.apply_to(&release.tag_name)
+                )
+                .as_str(),
+            )
+            .unwrap();
+        } else {
+            term.write_line(
+                format!(
+                    "{} {}",
+                    prefix_style.apply_to("Updating to"),
+                    version_style.apply_to(&release.tag_name)
+                )
+                .as_str(),
+            )
+            .unwrap();
+            let install = Install::new(
+                release.tag_name.clone(),
+                outdated.iter().map(|(target, _)| *target).collect(),
+                self.skip_sha_check,
+                self.source.clone(),
+                self.resume,
+                false,
+                self.dry_run,
+                self.config.clone(),
+            );
+            install
+                .install_release(&client, &repo, &release, &outdated)
+                .await?;
+        }
+
+        if self.remove_old {
+            // Anything older than the newest release has been superseded
+            let mut manifest =
+                Manifest::load(&data_directory).context("Failed to load install manifest")?;
+            let source = self.source.to_string();
+            let mut failed = 0;
+            for (target, name, path) in superseded {
+                let usage = usages
+                    .iter()
+                    .find(|(t, _)| *t == target)
+                    .map(|(_, usage)| usage)
+                    .unwrap();
+                if !may_remove(&manifest, &self.source, target, &path, usage, self.force) {
+                    continue;
+                }
+                if self.dry_run {
+                    would(
+                        &self.config,
+                        "delete",
+                        path.display(),
+                        Some(utils::directory_size(&path)),
+                    );
+                    continue;
+                }
+                term.write_line(format!("{} {}", prefix_style.apply_to("Removing"), name).as_str())
+                    .unwrap();
+                // Builds stay in the manifest until they're gone so a failed delete can be
+                // retried
+                if let Err(e) = utils::remove_entry(&path) {
+                    eprintln!("{}", e);
+                    failed += 1;
+                    continue;
+                }
+                if let Some(directory) = path.file_name().and_then(|n| n.to_str()) {
+                    manifest.remove(&source, target, directory);
+                }
+            }
+            if !self.dry_run {
+                manifest
+                    .save(&data_directory)
+                    .context("Failed to save install manifest")?;
+            }
+            if failed > 0 {
+                return Err(anyhow::anyhow!("Failed to remove {} old builds", failed));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Update {
+    // Whether the newest release is installed in a target and the installs it supersedes there
+    // as (name, path)
+    fn check_target(
+        &self,
+        release: &Release,
+        target: Target,
+        compat_directory: &PathBuf,
+        data_directory: &Path,
+    ) -> anyhow::Result<(bool, Vec<(String, PathBuf)>)> {
+        // Nothing has been installed to a directory a dry run didn't create
+        if !compat_directory.exists() {
+            return Ok((false, Vec::new()));
+        }
+        Ok(match self.source.install_type() {
+            Some(install_type) => {
+                let newest = Version::parse(install_type, &release.tag_name).ok_or(
+                    anyhow::anyhow!("Failed to parse release tag: {}", release.tag_name),
+                )?;
+                let installed =
+                    version_info::get_installed_releases(install_type, compat_directory)
+                        .context("Failed to get directory entries")?;
+                (
+                    installed.iter().any(|(v, _)| *v == newest),
+                    installed
+                        .iter()
+                        .filter(|(v, _)| *v < newest)
+                        .map(|(v, e)| (v.to_string(), e.path()))
+                        .collect(),
+                )
+            }
+            None => {
+                // Custom sources can't be ordered by version so go by what the manifest
+                // recorded instead
+                let manifest =
+                    Manifest::load(data_directory).context("Failed to load install manifest")?;
+                let source = self.source.to_string();
+                let installed: Vec<_> = manifest
+                    .installs
+                    .iter()
+                    .filter(|e| e.source == source && e.target == target)
+                    .filter(|e| compat_directory.join(&e.directory).exists())
+                    .collect();
+                (
+                    installed.iter().any(|e| e.tag == release.tag_name),
+                    installed
+                        .iter()
+                        .filter(|e| e.tag != release.tag_name)
+                        .map(|e| (e.tag.clone(), compat_directory.join(&e.directory)))
+                        .collect(),
+                )
+            }
+        })
+    }
+}
@@ -0,0 +1,882 @@
+// End to end tests running protonctl against a local mock of the GitHub releases api
+mod common;
+
+use common::{mock_github, stderr, stdout, Fixture, Home};
+use std::os::unix::fs::MetadataExt;
+
+fn fixtures() -> Vec<Fixture> {
+    vec![
+        Fixture::proton("GE-Proton8-26"),
+        Fixture::proton("GE-Proton8-25"),
+    ]
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn can_list_remote_releases() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("list-remote");
+
+    let output = home.protonctl(&server, &["list"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    assert!(stdout.contains("GE-Proton8-26"));
+    assert!(stdout.contains("GE-Proton8-25"));
+    assert!(stdout.contains("Changelog for GE-Proton8-25"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn can_install_list_and_remove() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("install-remove");
+    let installed = home.steam_compat_directory().join("GE-Proton8-25");
+
+    let output = home.protonctl(&server, &["install", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(installed.join("proton").exists());
+    assert!(installed.join("compatibilitytool.vdf").exists());
+    let manifest = std::fs::read_to_string(home.manifest()).unwrap();
+    assert!(manifest.contains("GE-Proton8-25"));
+
+    let output = home.protonctl(&server, &["list", "--local"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("GE-Proton8-25 (managed)"));
+
+    let output = home.protonctl(&server, &["remove", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!installed.exists());
+    let manifest = std::fs::read_to_string(home.manifest()).unwrap();
+    assert!(!manifest.contains("GE-Proton8-25"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn can_install_latest() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("install-latest");
+
+    let output = home.protonctl(&server, &["install", "latest"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Resolved latest to"));
+    assert!(home
+        .steam_compat_directory()
+        .join("GE-Proton8-26/proton")
+        .exists());
+
+    let output = home.protonctl(&server, &["install", "latest~1"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(home
+        .steam_compat_directory()
+        .join("GE-Proton8-25/proton")
+        .exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn latest_skips_prereleases() {
+    let mut fixtures = fixtures();
+    let mut prerelease = Fixture::proton("GE-Proton9-1");
+    prerelease.prerelease = true;
+    fixtures.insert(0, prerelease);
+    let server = mock_github(&fixtures).await;
+    let home = Home::new("latest-prerelease");
+
+    let output = home.protonctl(&server, &["install", "latest"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(home.steam_compat_directory().join("GE-Proton8-26").exists());
+
+    let output = home.protonctl(&server, &["install", "latest~1"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(home.steam_compat_directory().join("GE-Proton8-25").exists());
+    assert!(!home.steam_compat_directory().join("GE-Proton9-1").exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn hash_mismatch_is_not_installed() {
+    let mut fixtures = fixtures();
+    fixtures[1].sha512sum = Some(format!("{}  GE-Proton8-25.tar.gz\n", "0".repeat(128)));
+    let server = mock_github(&fixtures).await;
+    let home = Home::new("hash-mismatch");
+
+    let output = home.protonctl(&server, &["install", "GE-Proton8-25"]);
+    assert_eq!(output.status.code(), Some(7), "{}", stderr(&output));
+    assert!(!home.steam_compat_directory().join("GE-Proton8-25").exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn missing_release_is_not_found() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("not-found");
+
+    let output = home.protonctl(&server, &["install", "GE-Proton1-1"]);
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn foreign_installs_need_force_to_remove() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("remove-foreign");
+    let foreign = home.steam_compat_directory().join("GE-Proton7-1");
+    std::fs::create_dir_all(&foreign).unwrap();
+
+    let output = home.protonctl(&server, &["list", "--local"]);
+    assert!(stdout(&output).contains("GE-Proton7-1 (foreign)"));
+
+    let output = home.protonctl(&server, &["remove", "GE-Proton7-1"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(foreign.exists());
+
+    let output = home.protonctl(&server, &["remove", "--force", "GE-Proton7-1"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!foreign.exists());
+}
+
+const KRON4EK_CONFIG: &str = r#"
+[[sources]]
+name = "kron4ek"
+owner = "Kron4ek"
+repo = "Wine-Builds"
+asset_pattern = "wine-*-amd64.tar.gz"
+directory = ".local/share/lutris/runners/wine"
+"#;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn can_install_and_update_custom_sources() {
+    let server = mock_github(&[
+        Fixture::wine("Kron4ek/Wine-Builds", "9.1"),
+        Fixture::wine("Kron4ek/Wine-Builds", "9.0"),
+    ])
+    .await;
+    let home = Home::new("custom-source");
+    home.write_config(KRON4EK_CONFIG);
+    let wine = home.path.join(".local/share/lutris/runners/wine");
+
+    let output = home.protonctl(&server, &["list", "-t", "kron4ek"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("9.1"));
+
+    let output = home.protonctl(&server, &["install", "-t", "kron4ek", "9.0"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Skipping hash check"));
+    assert!(wine.join("wine-9.0-amd64/bin/wine").exists());
+
+    let output = home.protonctl(&server, &["update", "-t", "kron4ek", "--remove-old"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(wine.join("wine-9.1-amd64/bin/wine").exists());
+    assert!(!wine.join("wine-9.0-amd64").exists());
+
+    let output = home.protonctl(&server, &["list", "-t", "kron4ek", "--local"]);
+    assert!(stdout(&output).contains("wine-9.1-amd64 (managed)"));
+
+    let output = home.protonctl(&server, &["remove", "-t", "kron4ek", "9.1"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!wine.join("wine-9.1-amd64").exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unknown_types_are_config_errors() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("unknown-type");
+
+    let output = home.protonctl(&server, &["list", "-t", "kron4ek"]);
+    assert_eq!(output.status.code(), Some(10), "{}", stderr(&output));
+
+    home.write_config("[[sources]]\nname = \"kron4ek\"\n");
+    let output = home.protonctl(&server, &["list", "-t", "kron4ek"]);
+    assert_eq!(output.status.code(), Some(10), "{}", stderr(&output));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn can_get_and_set_config() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("config");
+
+    let output = home.protonctl(&server, &["config", "path"]);
+    let path = stdout(&output);
+    assert_eq!(
+        path.trim(),
+        home.path
+            .join(".config/protonctl/config.toml")
+            .to_string_lossy()
+    );
+
+    let output = home.protonctl(
+        &server,
+        &["config", "set", "defaults.number", "1", "--dry-run"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Would write"));
+    assert!(!home.path.join(".config/protonctl/config.toml").exists());
+    let output = home.protonctl(
+        &server,
+        &["config", "set", "defaults.number", "many", "--dry-run"],
+    );
+    assert_eq!(output.status.code(), Some(10), "{}", stderr(&output));
+
+    let output = home.protonctl(&server, &["config", "set", "defaults.number", "1"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = home.protonctl(&server, &["config", "get", "defaults.number"]);
+    assert_eq!(stdout(&output).trim(), "1");
+    let output = home.protonctl(&server, &["list"]);
+    assert!(stdout(&output).contains("GE-Proton8-26"));
+    assert!(!stdout(&output).contains("GE-Proton8-25"));
+
+    let output = home.protonctl(&server, &["config", "set", "api.token", "secret"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = home.protonctl(&server, &["config", "show"]);
+    assert!(stdout(&output).contains("number = 1"));
+    assert!(stdout(&output).contains("compatibilitytools.d"));
+    assert!(!stdout(&output).contains("secret"));
+
+    for (key, value) in [
+        ("defaults.number", "0"),
+        ("defaults.type", "kron4ek"),
+        ("styles.url", "sparkly"),
+        ("paths.steam", "/games"),
+    ] {
+        let output = home.protonctl(&server, &["config", "set", key, value]);
+        assert_eq!(output.status.code(), Some(10), "{}", stderr(&output));
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn can_install_to_configured_paths() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("config-paths");
+    let config = home.path.join("protonctl.toml");
+    std::fs::write(
+        &config,
+        "[paths]\ndownload = \"downloads\"\ndata = \"state\"\nproton = \"proton-builds\"\n",
+    )
+    .unwrap();
+    let config = config.to_string_lossy();
+
+    let output = home.protonctl(&server, &["--config", &config, "install", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(home
+        .path
+        .join("proton-builds/GE-Proton8-25/proton")
+        .exists());
+    assert!(home.path.join("state/manifest.json").exists());
+    assert!(!home.manifest().exists());
+
+    let output = home.protonctl(&server, &["--config", "missing.toml", "list"]);
+    assert_eq!(output.status.code(), Some(10), "{}", stderr(&output));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn installs_to_the_detected_steam_root() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("steam-root");
+    let root = home.path.join("games/SteamLibrary");
+    std::fs::create_dir_all(root.join("steamapps")).unwrap();
+    std::fs::create_dir_all(home.path.join(".steam")).unwrap();
+    std::os::unix::fs::symlink(&root, home.path.join(".steam/root")).unwrap();
+
+    let output = home.protonctl(&server, &["install", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(root
+        .join("compatibilitytools.d/GE-Proton8-25/proton")
+        .exists());
+    assert!(!home.steam_compat_directory().exists());
+    assert!(home.manifest().exists());
+    assert!(home.path.join(".cache/protonctl").is_dir());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn chooses_between_detected_targets() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("targets");
+    let flatpak = home
+        .path
+        .join(".var/app/com.valvesoftware.Steam/.local/share/Steam/compatibilitytools.d");
+    let snap = home
+        .path
+        .join("snap/steam/common/.local/share/Steam/compatibilitytools.d");
+    std::fs::create_dir_all(home.path.join(".local/share/Steam/steamapps")).unwrap();
+    std::fs::create_dir_all(home.path.join(".var/app/com.valvesoftware.Steam")).unwrap();
+
+    let output = home.protonctl(&server, &["install", "GE-Proton8-25"]);
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    assert!(stderr(&output).contains("native, flatpak"));
+
+    let output = home.protonctl(&server, &["install", "--target", "all", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(home
+        .steam_compat_directory()
+        .join("GE-Proton8-25/proton")
+        .exists());
+    assert!(flatpak.join("GE-Proton8-25/proton").exists());
+
+    let output = home.protonctl(&server, &["--target", "snap", "install", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(snap.join("GE-Proton8-25/proton").exists());
+
+    let output = home.protonctl(&server, &["list", "--local", "--target", "all"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("proton installs (flatpak):"));
+
+    let output = home.protonctl(&server, &["-f", "remove", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!flatpak.join("GE-Proton8-25").exists());
+    assert!(home.steam_compat_directory().join("GE-Proton8-25").exists());
+
+    let output = home.protonctl(&server, &["-t", "ulwgl", "-f", "list", "--local"]);
+    assert_eq!(output.status.code(), Some(10), "{}", stderr(&output));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn installs_one_download_into_several_targets() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("multi-target");
+    let flatpak = home
+        .path
+        .join(".var/app/com.valvesoftware.Steam/.local/share/Steam/compatibilitytools.d");
+
+    let output = home.protonctl(
+        &server,
+        &[
+            "install",
+            "--target",
+            "native",
+            "--target",
+            "flatpak",
+            "GE-Proton8-25",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let native = home.steam_compat_directory().join("GE-Proton8-25/proton");
+    assert!(native.exists());
+    assert!(flatpak.join("GE-Proton8-25/proton").exists());
+    assert_eq!(std::fs::metadata(&native).unwrap().nlink(), 2);
+
+    // GE-Proton8-25 is the second fixture so its tarball is asset 20
+    let downloads = server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter(|r| r.url.path().ends_with("/assets/20"))
+        .count();
+    assert_eq!(downloads, 1);
+    let manifest = std::fs::read_to_string(home.manifest()).unwrap();
+    assert!(manifest.contains("\"native\"") && manifest.contains("\"flatpak\""));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn records_targets_installed_before_a_failure() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("multi-target-failure");
+    // A file where the flatpak's compatibility directory should be can't be installed into
+    let flatpak = home
+        .path
+        .join(".var/app/com.valvesoftware.Steam/.local/share/Steam/compatibilitytools.d");
+    std::fs::create_dir_all(flatpak.parent().unwrap()).unwrap();
+    std::fs::write(&flatpak, "").unwrap();
+
+    let output = home.protonctl(
+        &server,
+        &[
+            "install",
+            "--target",
+            "native",
+            "--target",
+            "flatpak",
+            "GE-Proton8-25",
+        ],
+    );
+    assert!(!output.status.success());
+    assert!(home
+        .steam_compat_directory()
+        .join("GE-Proton8-25/proton")
+        .exists());
+    let output = home.protonctl(&server, &["--target", "native", "list", "--local"]);
+    assert!(stdout(&output).contains("GE-Proton8-25 (managed)"));
+    assert!(!home
+        .path
+        .join(".cache/protonctl/GE-Proton8-25.tar.gz")
+        .exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn can_set_steam_compat_tools() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("steam-tools");
+    home.write_steam_config();
+    let steam = home.path.join(".local/share/Steam");
+    std::fs::write(
+        steam.join("steamapps/appmanifest_440.acf"),
+        "\"AppState\"\n{\n\t\"appid\"\t\t\"440\"\n\t\"name\"\t\t\"Team Fortress 2\"\n}\n",
+    )
+    .unwrap();
+
+    let original = std::fs::read_to_string(steam.join("config/config.vdf")).unwrap();
+    let output = home.protonctl(
+        &server,
+        &["--dry-run", "steam", "set-tool", "440", "GE-Proton8-25"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Would write"));
+    let config = std::fs::read_to_string(steam.join("config/config.vdf")).unwrap();
+    assert_eq!(config, original);
+    assert_eq!(std::fs::read_dir(steam.join("config")).unwrap().count(), 1);
+
+    let output = home.protonctl(
+        &server,
+        &["steam", "set-tool", "team fortress", "GE-Proton8-25"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = std::fs::read_to_string(steam.join("config/config.vdf")).unwrap();
+    assert!(config.contains("\"440\"\n\t\t\t\t\t{\n\t\t\t\t\t\t\"name\"\t\t\"GE-Proton8-25\""));
+    let backups = std::fs::read_dir(steam.join("config"))
+        .unwrap()
+        .filter(|e| e.as_ref().unwrap().path().extension().unwrap() == "bak")
+        .count();
+    assert_eq!(backups, 1);
+
+    let output = home.protonctl(&server, &["steam", "show"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Default: proton_experimental"));
+    assert!(stdout(&output).contains("Team Fortress 2 (440): GE-Proton8-25"));
+
+    let output = home.protonctl(&server, &["steam", "unset-tool", "440"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = home.protonctl(&server, &["steam", "show"]);
+    assert!(!stdout(&output).contains("GE-Proton8-25"));
+
+    let output = home.protonctl(&server, &["steam", "set-tool", "Portal", "GE-Proton8-25"]);
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn can_set_the_default_steam_tool() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("steam-default");
+    let config = home.write_steam_config();
+
+    let output = home.protonctl(&server, &["install", "--set-default", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let contents = std::fs::read_to_string(&config).unwrap();
+    assert!(contents.contains("\"0\"\n\t\t\t\t\t{\n\t\t\t\t\t\t\"name\"\t\t\"GE-Proton8-25\""));
+
+    // The internal name from compatibilitytool.vdf is what Steam goes by
+    let tool = home.steam_compat_directory().join("GE-Proton8-25");
+    let vdf = std::fs::read_to_string(tool.join("compatibilitytool.vdf")).unwrap();
+    std::fs::write(
+        tool.join("compatibilitytool.vdf"),
+        vdf.replacen("\"GE-Proton8-25\" //", "\"GE-Proton8-25-patched\" //", 1),
+    )
+    .unwrap();
+    let output = home.protonctl(&server, &["steam", "default", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = home.protonctl(&server, &["steam", "show"]);
+    assert!(stdout(&output).contains("Default: GE-Proton8-25-patched"));
+
+    let output = home.protonctl(&server, &["steam", "default", "GE-Proton8-26"]);
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+    let output = home.protonctl(
+        &server,
+        &["-t", "wine", "install", "--set-default", "GE-Proton8-25"],
+    );
+    assert_eq!(output.status.code(), Some(10), "{}", stderr(&output));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn protects_tools_games_still_use() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("steam-usage");
+    home.write_steam_config();
+    let steam = home.path.join(".local/share/Steam");
+    std::fs::write(
+        steam.join("steamapps/appmanifest_440.acf"),
+        "\"AppState\"\n{\n\t\"appid\"\t\t\"440\"\n\t\"name\"\t\t\"Team Fortress 2\"\n}\n",
+    )
+    .unwrap();
+    let output = home.protonctl(&server, &["install", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = home.protonctl(
+        &server,
+        &["steam", "set-tool", "Team Fortress 2", "GE-Proton8-25"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = home.protonctl(&server, &["list", "--local"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("GE-Proton8-25 (managed)\n    Team Fortress 2 (440)\n"));
+
+    // A truncated appmanifest of another game doesn't hide that 440 uses the tool
+    std::fs::write(
+        steam.join("steamapps/appmanifest_730.acf"),
+        "\"AppState\"\n{\n\t\"appid\"\t\t\"730\"\n\t\"name\"",
+    )
+    .unwrap();
+    let tool = home.steam_compat_directory().join("GE-Proton8-25");
+    let output = home.protonctl(&server, &["remove", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("appmanifest_730.acf"));
+    assert!(stderr(&output).contains("used by Team Fortress 2 (440)"));
+    assert!(tool.is_dir());
+
+    // Nothing is removed when it's unknown which tools are in use
+    let config = steam.join("config/config.vdf");
+    let contents = std::fs::read_to_string(&config).unwrap();
+    std::fs::write(&config, &contents[..contents.len() / 2]).unwrap();
+    for args in [
+        &["remove", "GE-Proton8-25"][..],
+        &["prune", "--keep", "0", "--yes"][..],
+    ] {
+        let output = home.protonctl(&server, args);
+        assert!(!output.status.success());
+        assert!(
+            stderr(&output).contains("Use --force"),
+            "{}",
+            stderr(&output)
+        );
+        assert!(tool.is_dir());
+    }
+    std::fs::write(&config, contents).unwrap();
+
+    let output = home.protonctl(&server, &["remove", "--force", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!tool.exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn update_keeps_old_builds_in_use() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("update-usage");
+    home.write_steam_config();
+    let compat = home.steam_compat_directory();
+    let output = home.protonctl(&server, &["install", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = home.protonctl(&server, &["steam", "set-tool", "440", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    std::fs::create_dir_all(compat.join("GE-Proton8-4")).unwrap();
+
+    let output = home.protonctl(&server, &["update", "--remove-old"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(compat.join("GE-Proton8-26").is_dir());
+    assert!(stderr(&output).contains("\"GE-Proton8-25\": used by Unknown app (440)"));
+    assert!(stderr(&output).contains("\"GE-Proton8-4\": not installed by protonctl"));
+    assert!(compat.join("GE-Proton8-25").is_dir());
+    assert!(compat.join("GE-Proton8-4").is_dir());
+
+    let output = home.protonctl(&server, &["update", "--remove-old", "--force"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!compat.join("GE-Proton8-25").exists());
+    assert!(!compat.join("GE-Proton8-4").exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn can_set_lutris_wine_versions() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("lutris");
+    let lutris = home.path.join(".local/share/lutris");
+    std::fs::create_dir_all(lutris.join("runners/wine/lutris-GE-Proton8-26-x86_64")).unwrap();
+    std::fs::create_dir_all(lutris.join("games")).unwrap();
+    let connection = rusqlite::Connection::open(lutris.join("pga.db")).unwrap();
+    connection
+        .execute_batch(
+            "CREATE TABLE games (id INTEGER PRIMARY KEY, name TEXT, slug TEXT, runner TEXT, configpath TEXT);
+             INSERT INTO games VALUES (1, 'Diablo IV', 'diablo-iv', 'wine', 'diablo-iv-1');",
+        )
+        .unwrap();
+    drop(connection);
+    let game = lutris.join("games/diablo-iv-1.yml");
+    std::fs::write(
+        &game,
+        "game:\n  exe: drive_c/Diablo IV Launcher.exe\nwine:\n  version: lutris-7.2-2-x86_64\n",
+    )
+    .unwrap();
+
+    let output = home.protonctl(&server, &["lutris", "show"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Diablo IV (diablo-iv): lutris-7.2-2-x86_64"));
+
+    let original = std::fs::read_to_string(&game).unwrap();
+    let output = home.protonctl(
+        &server,
+        &[
+            "--dry-run",
+            "lutris",
+            "set-version",
+            "diablo",
+            "GE-Proton8-26",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Would write"));
+    assert_eq!(std::fs::read_to_string(&game).unwrap(), original);
+    assert_eq!(std::fs::read_dir(lutris.join("games")).unwrap().count(), 1);
+
+    // Release tags are turned into the runner directory Lutris goes by
+    let output = home.protonctl(
+        &server,
+        &["lutris", "set-version", "diablo", "GE-Proton8-26"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let contents = std::fs::read_to_string(&game).unwrap();
+    assert!(contents.contains("version: lutris-GE-Proton8-26-x86_64"));
+    assert!(contents.contains("exe: drive_c/Diablo IV Launcher.exe"));
+
+    let output = home.protonctl(&server, &["lutris", "default", "GE-Proton8-26"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let runner = std::fs::read_to_string(lutris.join("runners/wine.yml")).unwrap();
+    assert_eq!(runner, "wine:\n  version: lutris-GE-Proton8-26-x86_64\n");
+
+    let output = home.protonctl(&server, &["lutris", "unset-version", "diablo-iv"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = home.protonctl(&server, &["lutris", "show"]);
+    assert!(stdout(&output).contains("Default: lutris-GE-Proton8-26-x86_64"));
+    assert!(stdout(&output).contains("Diablo IV (diablo-iv): default"));
+
+    // Every change to the game config left a backup
+    let backups = std::fs::read_dir(lutris.join("games"))
+        .unwrap()
+        .filter(|e| e.as_ref().unwrap().path().extension().unwrap() == "bak")
+        .count();
+    assert!(backups >= 1);
+    let output = home.protonctl(
+        &server,
+        &["lutris", "set-version", "Celeste", "GE-Proton8-26"],
+    );
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+
+    // A broken game config only leaves that game out
+    let connection = rusqlite::Connection::open(lutris.join("pga.db")).unwrap();
+    connection
+        .execute(
+            "INSERT INTO games VALUES (2, 'Hades', 'hades', 'wine', 'hades-2')",
+            [],
+        )
+        .unwrap();
+    drop(connection);
+    std::fs::write(lutris.join("games/hades-2.yml"), "wine: [version\n").unwrap();
+    let output = home.protonctl(&server, &["lutris", "show"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Skipping Hades"));
+    assert!(stdout(&output).contains("Diablo IV (diablo-iv): default"));
+
+    // It doesn't stop remove from seeing what the other games use
+    let output = home.protonctl(
+        &server,
+        &["-t", "wine", "remove", "lutris-GE-Proton8-26-x86_64"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("used by Default"));
+    assert!(lutris
+        .join("runners/wine/lutris-GE-Proton8-26-x86_64")
+        .is_dir());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn can_install_to_heroic() {
+    let server = mock_github(&fixtures()).await;
+    let home = Home::new("heroic");
+    home.write_steam_config();
+    std::fs::create_dir_all(home.path.join(".config/heroic")).unwrap();
+    let tools = home.path.join(".config/heroic/tools/proton");
+
+    let output = home.protonctl(&server, &["install", "GE-Proton8-25"]);
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    assert!(stderr(&output).contains("native, heroic"));
+
+    let output = home.protonctl(&server, &["--target", "heroic", "install", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(tools.join("GE-Proton8-25/proton").exists());
+    assert!(!home.steam_compat_directory().join("GE-Proton8-25").exists());
+
+    let output = home.protonctl(&server, &["--target", "heroic", "list", "--local"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("GE-Proton8-25 (managed)"));
+
+    // Steam commands only look at the installs of Steam
+    let output = home.protonctl(&server, &["steam", "show"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = home.protonctl(&server, &["--target", "heroic", "steam", "show"]);
+    assert_eq!(output.status.code(), Some(10), "{}", stderr(&output));
+
+    let output = home.protonctl(&server, &["--target", "heroic", "remove", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!tools.join("GE-Proton8-25").exists());
+
+    // Heroic has no Steam Play default, so it's refused before downloading or skipped
+    let output = home.protonctl(
+        &server,
+        &[
+            "--target",
+            "heroic",
+            "install",
+            "--set-default",
+            "GE-Proton8-26",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(10), "{}", stderr(&output));
+    assert!(!tools.join("GE-Proton8-26").exists());
+    assert!(!home
+        .path
+        .join(".cache/protonctl/GE-Proton8-26.tar.gz")
+        .exists());
+
+    let output = home.protonctl(
+        &server,
+        &[
+            "--target",
+            "all",
+            "install",
+            "--set-default",
+            "GE-Proton8-26",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Not setting the Steam Play default for heroic"));
+    assert!(tools.join("GE-Proton8-26/proton").exists());
+    let config =
+        std::fs::read_to_string(home.path.join(".local/share/Steam/config/config.vdf")).unwrap();
+    assert!(config.contains("\"GE-Proton8-26\""));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn can_install_wine_to_bottles() {
+    let server = mock_github(&[Fixture::wine_ge("GE-Proton8-26")]).await;
+    let home = Home::new("bottles");
+    let lutris = home.path.join(".local/share/lutris/runners/wine");
+    let bottles = home.path.join(".local/share/bottles/runners");
+    std::fs::create_dir_all(&lutris).unwrap();
+    std::fs::create_dir_all(&bottles).unwrap();
+
+    let output = home.protonctl(&server, &["-t", "wine", "install", "GE-Proton8-26"]);
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    assert!(stderr(&output).contains("native, bottles"));
+
+    // Bottles gets the release under its own name and Lutris under the archive's
+    let output = home.protonctl(
+        &server,
+        &["-t", "wine", "--target", "all", "install", "GE-Proton8-26"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(lutris.join("lutris-GE-Proton8-26-x86_64/bin/wine").exists());
+    assert!(bottles.join("wine-ge-proton8-26/bin/wine").exists());
+
+    let output = home.protonctl(
+        &server,
+        &["-t", "wine", "--target", "bottles", "list", "--local"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("wine-ge-proton8-26 (managed)"));
+
+    let output = home.protonctl(
+        &server,
+        &[
+            "-t",
+            "wine",
+            "--target",
+            "bottles",
+            "remove",
+            "GE-Proton8-26",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!bottles.join("wine-ge-proton8-26").exists());
+    assert!(lutris.join("lutris-GE-Proton8-26-x86_64").exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn can_prune_old_builds() {
+    let server = mock_github(&[
+        Fixture::proton("GE-Proton8-27"),
+        Fixture::proton("GE-Proton8-26"),
+        Fixture::proton("GE-Proton8-25"),
+        Fixture::proton("GE-Proton8-4"),
+    ])
+    .await;
+    let home = Home::new("prune");
+    home.write_steam_config();
+    let compat = home.steam_compat_directory();
+    for tag in [
+        "GE-Proton8-4",
+        "GE-Proton8-25",
+        "GE-Proton8-26",
+        "GE-Proton8-27",
+    ] {
+        let output = home.protonctl(&server, &["install", tag]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+    std::fs::create_dir_all(compat.join("Proton-tkg")).unwrap();
+    let output = home.protonctl(&server, &["steam", "default", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // Nothing is deleted without an answer
+    let output = home.protonctl(&server, &["prune", "--keep", "1"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Builds to remove:"));
+    assert!(stdout(&output).contains("Nothing removed"));
+    assert!(compat.join("GE-Proton8-26").exists());
+
+    // Ordered by version, so 8-4 is the oldest. 8-25 is Steam's default and 8-26 is pinned.
+    let output = home.protonctl(
+        &server,
+        &["prune", "--keep", "1", "--pin", "GE-Proton8-26", "--yes"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("GE-Proton8-4"));
+    assert!(stderr(&output).contains("\"GE-Proton8-25\": used by Default"));
+    assert!(stderr(&output).contains("\"GE-Proton8-26\": pinned"));
+    assert!(compat.join("GE-Proton8-27").exists());
+    assert!(compat.join("GE-Proton8-26").exists());
+    assert!(compat.join("GE-Proton8-25").exists());
+    assert!(!compat.join("GE-Proton8-4").exists());
+    assert!(compat.join("Proton-tkg").exists());
+
+    let output = home.protonctl(&server, &["prune", "--keep", "1", "--force", "-y"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!compat.join("GE-Proton8-26").exists());
+    assert!(!compat.join("GE-Proton8-25").exists());
+    let output = home.protonctl(&server, &["prune", "--keep", "1"]);
+    assert!(stdout(&output).contains("Nothing to prune"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dry_run_changes_nothing() {
+    let server = mock_github(&[
+        Fixture::proton("GE-Proton8-26"),
+        Fixture::proton("GE-Proton8-25"),
+    ])
+    .await;
+    let home = Home::new("dry-run");
+    home.write_steam_config();
+    let compat = home.steam_compat_directory();
+
+    let output = home.protonctl(&server, &["--dry-run", "install", "GE-Proton8-25"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains(&format!("Would download {}", server.uri())));
+    assert!(stdout(&output).contains("Would unpack into"));
+    assert!(!compat.exists());
+    assert!(!home.path.join(".cache/protonctl").exists());
+    assert!(!home.manifest().exists());
+
+    for tag in ["GE-Proton8-25", "GE-Proton8-26"] {
+        let output = home.protonctl(&server, &["install", tag]);
+        assert!(output.status.success(), "{}", stderr(&output));
+    }
+    let manifest = std::fs::read_to_string(home.manifest()).unwrap();
+
+    let output = home.protonctl(&server, &["prune", "--keep", "1", "--dry-run"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Would delete"));
+    assert!(compat.join("GE-Proton8-25").exists());
+
+    let output = home.protonctl(&server, &["remove", "--all", "--dry-run"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("GE-Proton8-26"));
+    assert!(compat.join("GE-Proton8-26").exists());
+    assert_eq!(std::fs::read_to_string(home.manifest()).unwrap(), manifest);
+
+    // --all asks first and stdin is closed, so nothing goes without --yes
+    let output = home.protonctl(&server, &["remove", "--all"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Nothing removed"));
+    assert!(compat.join("GE-Proton8-26").exists());
+
+    let output = home.protonctl(&server, &["remove", "--all", "--yes"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!compat.join("GE-Proton8-25").exists());
+    assert!(!compat.join("GE-Proton8-26").exists());
+}
@@ -0,0 +1,282 @@
+// Local stand-in for the GitHub releases api and a throwaway home directory to run protonctl in
+use flate2::{write::GzEncoder, Compression};
+use sha2::{Digest, Sha512};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+pub const PROTON_REPO: &str = "GloriousEggroll/proton-ge-custom";
+pub const WINE_REPO: &str = "GloriousEggroll/wine-ge-custom";
+
+// A release of repo with a tarball and, optionally, its sha512sum
+pub struct Fixture {
+    pub repo: String,
+    pub tag: String,
+    pub tarball_name: String,
+    pub tarball: Vec<u8>,
+    pub sha512sum: Option<String>,
+    pub prerelease: bool,
+}
+
+impl Fixture {
+    pub fn proton(tag: &str) -> Self {
+        let tarball_name = format!("{}.tar.gz", tag);
+        let compatibilitytool = format!(
+            "\"compatibilitytools\"\n{{\n  \"compat_tools\"\n  {{\n    \"{}\" // Internal name of this tool\n    {{\n      \"install_path\" \".\"\n      \"display_name\" \"{}\"\n    }}\n  }}\n}}\n",
+            tag, tag
+        );
+        let tarball = tarball(
+            tag,
+            &[
+                ("proton", "#!/bin/sh\n"),
+                ("compatibilitytool.vdf", &compatibilitytool),
+            ],
+        );
+        let sha512sum = format!("{:x}  {}\n", Sha512::digest(&tarball), tarball_name);
+        Self {
+            repo: PROTON_REPO.to_string(),
+            tag: tag.to_string(),
+            tarball_name,
+            tarball,
+            sha512sum: Some(sha512sum),
+            prerelease: false,
+        }
+    }
+
+    // A wine-ge-custom release, which unpacks to lutris-<tag>-x86_64
+    pub fn wine_ge(tag: &str) -> Self {
+        let tarball_name = format!("wine-lutris-{}-x86_64.tar.gz", tag);
+        let tarball = tarball(
+            &format!("lutris-{}-x86_64", tag),
+            &[("bin/wine", "#!/bin/sh\n")],
+        );
+        let sha512sum = format!("{:x}  {}\n", Sha512::digest(&tarball), tarball_name);
+        Self {
+            repo: WINE_REPO.to_string(),
+            tag: tag.to_string(),
+            tarball_name,
+            tarball,
+            sha512sum: Some(sha512sum),
+            prerelease: false,
+        }
+    }
+
+    // A wine build laid out like Kron4ek's, which publishes no checksums
+    pub fn wine(repo: &str, tag: &str) -> Self {
+        let directory = format!("wine-{}-amd64", tag);
+        Self {
+            repo: repo.to_string(),
+            tag: tag.to_string(),
+            tarball_name: format!("{}.tar.gz", directory),
+            tarball: tarball(&directory, &[("bin/wine", "#!/bin/sh\n")]),
+            sha512sum: None,
+            prerelease: false,
+        }
+    }
+
+    fn release_json(&self, tar_id: u64, sha_id: u64) -> serde_json::Value {
+        let mut assets = vec![serde_json::json!({
+            "name": self.tarball_name, "id": tar_id, "size": self.tarball.len()
+        })];
+        if let Some(sha512sum) = &self.sha512sum {
+            assets.insert(
+                0,
+                serde_json::json!({
+                    "name": format!("{}.sha512sum", self.tag), "id": sha_id, "size": sha512sum.len()
+                }),
+            );
+        }
+        serde_json::json!({
+            "html_url": format!("https://github.com/{}/releases/tag/{}", self.repo, self.tag),
+            "tag_name": self.tag,
+            "body": format!("Changelog for {}", self.tag),
+            "assets": assets,
+            "prerelease": self.prerelease,
+        })
+    }
+}
+
+// A gzipped tarball with the given files inside a single top level directory
+fn tarball(directory: &str, files: &[(&str, &str)]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::fast()));
+    for (name, contents) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder
+            .append_data(
+                &mut header,
+                format!("{}/{}", directory, name),
+                contents.as_bytes(),
+            )
+            .unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap()
+}
+
+// Release listing that honours per_page and page like the real endpoint
+struct Listing(Vec<serde_json::Value>);
+
+impl Respond for Listing {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let query = |name: &str, default: usize| -> usize {
+            request
+                .url
+                .query_pairs()
+                .find(|(key, _)| key == name)
+                .and_then(|(_, value)| value.parse().ok())
+                .unwrap_or(default)
+        };
+        let per_page = query("per_page", 30);
+        let page = query("page", 1).max(1);
+        let releases: Vec<&serde_json::Value> = self
+            .0
+            .iter()
+            .skip((page - 1) * per_page)
+            .take(per_page)
+            .collect();
+        ResponseTemplate::new(200).set_body_json(releases)
+    }
+}
+
+// Serve the releases of each repo newest first, the way GitHub lists them
+pub async fn mock_github(releases: &[Fixture]) -> MockServer {
+    let server = MockServer::start().await;
+    let mut repos: Vec<&str> = releases.iter().map(|r| r.repo.as_str()).collect();
+    repos.sort();
+    repos.dedup();
+    for repo in repos {
+        let base = format!("/repos/{}/releases", repo);
+        // Asset ids have to be unique across releases, so derive them from the release index
+        let listing: Vec<(&Fixture, u64, serde_json::Value)> = releases
+            .iter()
+            .enumerate()
+            .filter(|(_, release)| release.repo == repo)
+            .map(|(index, release)| {
+                let tar_id = (index as u64 + 1) * 10;
+                (release, tar_id, release.release_json(tar_id, tar_id + 1))
+            })
+            .collect();
+        let json: Vec<&serde_json::Value> = listing.iter().map(|(_, _, json)| json).collect();
+        Mock::given(method("GET"))
+            .and(path(base.clone()))
+            .respond_with(Listing(json.iter().map(|v| (*v).clone()).collect()))
+            .mount(&server)
+            .await;
+        // Like GitHub, the latest release is the newest one that isn't a prerelease
+        if let Some((_, _, latest)) = listing.iter().find(|(release, _, _)| !release.prerelease) {
+            Mock::given(method("GET"))
+                .and(path(format!("{}/latest", base)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(latest))
+                .mount(&server)
+                .await;
+        }
+        for (release, tar_id, json) in &listing {
+            Mock::given(method("GET"))
+                .and(path(format!("{}/tags/{}", base, release.tag)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json))
+                .mount(&server)
+                .await;
+            Mock::given(method("GET"))
+                .and(path(format!("{}/assets/{}", base, tar_id)))
+                .respond_with(ResponseTemplate::new(200).set_body_bytes(release.tarball.clone()))
+                .mount(&server)
+                .await;
+            if let Some(sha512sum) = &release.sha512sum {
+                Mock::given(method("GET"))
+                    .and(path(format!("{}/assets/{}", base, tar_id + 1)))
+                    .respond_with(ResponseTemplate::new(200).set_body_string(sha512sum))
+                    .mount(&server)
+                    .await;
+            }
+        }
+    }
+    // Anything else is a missing release
+    Mock::given(method("GET"))
+        .respond_with(
+            ResponseTemplate::new(404).set_body_json(serde_json::json!({"message": "Not Found"})),
+        )
+        .mount(&server)
+        .await;
+    server
+}
+
+// A home directory that is deleted again when the test finishes
+pub struct Home {
+    pub path: PathBuf,
+}
+
+impl Home {
+    pub fn new(test: &str) -> Self {
+        let mut path = std::env::temp_dir();
+        path.push(format!("protonctl-it-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+
+    pub fn steam_compat_directory(&self) -> PathBuf {
+        self.path.join(".local/share/Steam/compatibilitytools.d")
+    }
+
+    pub fn write_config(&self, contents: &str) {
+        let directory = self.path.join(".config/protonctl");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("config.toml"), contents).unwrap();
+    }
+
+    // Write config/config.vdf for a native Steam with the default tool set to proton_experimental
+    pub fn write_steam_config(&self) -> PathBuf {
+        let steam = self.path.join(".local/share/Steam");
+        std::fs::create_dir_all(steam.join("config")).unwrap();
+        std::fs::create_dir_all(steam.join("steamapps")).unwrap();
+        let path = steam.join("config/config.vdf");
+        std::fs::write(
+            &path,
+            "\"InstallConfigStore\"\n{\n\t\"Software\"\n\t{\n\t\t\"Valve\"\n\t\t{\n\t\t\t\"Steam\"\n\t\t\t{\n\t\t\t\t\"CompatToolMapping\"\n\t\t\t\t{\n\t\t\t\t\t\"0\"\n\t\t\t\t\t{\n\t\t\t\t\t\t\"name\"\t\t\"proton_experimental\"\n\t\t\t\t\t\t\"config\"\t\t\"\"\n\t\t\t\t\t\t\"priority\"\t\t\"75\"\n\t\t\t\t\t}\n\t\t\t\t}\n\t\t\t}\n\t\t}\n\t}\n}\n",
+        )
+        .unwrap();
+        path
+    }
+
+    pub fn manifest(&self) -> PathBuf {
+        self.path.join(".local/share/protonctl/manifest.json")
+    }
+
+    // Run protonctl with this home directory against the mock server
+    pub fn protonctl(&self, server: &MockServer, args: &[&str]) -> Output {
+        protonctl(&self.path, &server.uri(), args)
+    }
+}
+
+impl Drop for Home {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+pub fn protonctl(home: &Path, api_url: &str, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_protonctl-rs"))
+        .args(args)
+        .env("HOME", home)
+        .env("PROTONCTL_API_URL", api_url)
+        // Keep the user's own directories out of the way of HOME
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("XDG_DATA_HOME")
+        .env_remove("XDG_CACHE_HOME")
+        .env_remove("PROTONCTL_CONFIG")
+        .env_remove("GITHUB_TOKEN")
+        .env_remove("GH_TOKEN")
+        .output()
+        .unwrap()
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}